@@ -55,6 +55,27 @@
 //!     delta_y: 1,
 //! });
 //! ```
+//!
+//! `simulate` only sends physical keys on a QWERTY mapping. To type arbitrary
+//! Unicode text (accents, emoji, non-Latin scripts) regardless of layout, use
+//! `simulate_text` instead:
+//!
+//! ```no_run
+//! use rdev::simulate_text;
+//!
+//! simulate_text("héllo 👋").unwrap();
+//! ```
+//!
+//! `EventType::MouseMove` teleports the cursor in one jump, which looks
+//! unnatural and breaks software that tracks intermediate motion. Use
+//! `simulate_mouse_move_smooth` to move there over time instead:
+//!
+//! ```no_run
+//! use rdev::simulate_mouse_move_smooth;
+//! use std::time::Duration;
+//!
+//! simulate_mouse_move_smooth(400.0, 400.0, Duration::from_millis(500)).unwrap();
+//! ```
 //! # Main structs
 //! ## Event
 //!
@@ -156,45 +177,95 @@
 //! // string == Some("s")
 //! ```
 //!
+//! # Polling device state
+//!
+//! `listen` is callback-driven and needs a dedicated thread. If you just want
+//! to know what's held down right now (e.g. once per frame in a game loop),
+//! use `DeviceState` instead:
+//!
+//! ```no_run
+//! use rdev::DeviceState;
+//!
+//! let device_state = DeviceState::new();
+//! println!("{:?}", device_state.get_keys());
+//! println!("{:?}", device_state.get_mouse().coords);
+//! ```
+//!
 //! # Grabbing global events. (Requires `unstable_grab` feature)
 //!
-//! In the callback, returning None ignores the event
-//! and returning the event let's it pass. There is no modification of the event
-//! possible here.
+//! In the callback, returning `None` drops the event, returning `Some(event)`
+//! lets it pass, and returning `Some(modified_event)` replaces the event
+//! with `modified_event` before it reaches other applications. This makes
+//! `grab` usable as a remapping/interception layer (e.g. Caps Lock -> Esc)
+//! and not just a binary pass/drop filter, on platforms where `grab` is
+//! actually implemented.
 //! Caveat: On MacOS, you require the grab
 //! loop needs to be the primary app (no fork before) and need to have accessibility
 //! settings enabled.
-//! **Not implemented on Linux, you will always receive an error.**
+//! On Linux, this grabs the raw `/dev/input/event*` devices via evdev and
+//! re-emits events through a `uinput` virtual device, so it requires
+//! membership in the `input` group (or running elevated); if the devices
+//! can't be opened or grabbed you get `GrabError` back instead.
+//! On MacOS, this installs a `CGEventTap`, which can hand a rewritten event
+//! straight back to the tap. On Windows, the equivalent `WH_KEYBOARD_LL`/
+//! `WH_MOUSE_LL` hooks can only pass an event through or suppress it, so a
+//! `modified_event` is instead suppressed and re-injected the same way
+//! `simulate` would send it.
 //!
 //! # Serialization
 //!
 //! Serialization and deserialization. (Requires `serialize` feature).
+//!
+//! # Recording and replaying macros. (Requires `serialize` feature)
+//!
+//! `Recorder` wraps `listen` to capture a sequence of events together with
+//! the delay since the previous one, and `Player` replays a `Recording`
+//! through `simulate`, sleeping the recorded delay (scaled by an optional
+//! speed multiplier) between each one. See `Recorder` and `Player` for a
+//! runnable example.
 mod rdev;
 pub use crate::rdev::{
     Button, DisplayError, Event, EventType, GrabCallback, GrabError, Key, KeyboardState,
-    ListenError, SimulateError,
+    ListenError, MouseState, SimulateError,
 };
 
+#[cfg(feature = "serialize")]
+mod recorder;
+#[cfg(feature = "serialize")]
+pub use crate::recorder::{Player, Recorder, Recording};
+
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(target_os = "macos")]
 pub use crate::macos::Keyboard;
 #[cfg(target_os = "macos")]
-use crate::macos::{display_size as _display_size, listen as _listen, simulate as _simulate};
+use crate::macos::{
+    display_size as _display_size, listen as _listen, query_keys as _query_keys,
+    query_mouse as _query_mouse, simulate as _simulate, simulate_unicode as _simulate_unicode,
+};
+
+#[cfg(all(target_os = "linux", feature = "unstable_grab"))]
+mod linux_evdev;
 
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "linux")]
 pub use crate::linux::Keyboard;
 #[cfg(target_os = "linux")]
-use crate::linux::{display_size as _display_size, listen as _listen, simulate as _simulate};
+use crate::linux::{
+    display_size as _display_size, listen as _listen, query_keys as _query_keys,
+    query_mouse as _query_mouse, simulate as _simulate, simulate_unicode as _simulate_unicode,
+};
 
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
 pub use crate::windows::Keyboard;
 #[cfg(target_os = "windows")]
-use crate::windows::{display_size as _display_size, listen as _listen, simulate as _simulate};
+use crate::windows::{
+    display_size as _display_size, listen as _listen, query_keys as _query_keys,
+    query_mouse as _query_mouse, simulate as _simulate, simulate_unicode as _simulate_unicode,
+};
 
 /// Listening to global events. Caveat: On MacOS, you require the listen
 /// loop needs to be the primary app (no fork before) and need to have accessibility
@@ -260,6 +331,82 @@ pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
     _simulate(event_type)
 }
 
+/// Types arbitrary Unicode text, independent of the current keyboard layout.
+///
+/// `simulate` only knows about physical `Key`s on a QWERTY mapping, so
+/// sending characters like "é", "ß" or emoji through it requires manual
+/// dead-key/modifier gymnastics and often just fails. `simulate_text`
+/// injects the text directly: on Windows via `SendInput` with
+/// `KEYEVENTF_UNICODE`, on MacOS via `CGEventKeyboardSetUnicodeString`, and
+/// on X11 by temporarily remapping the highest keycode to each character's
+/// keysym and pressing it.
+///
+/// ```no_run
+/// use rdev::simulate_text;
+///
+/// simulate_text("héllo 👋").unwrap();
+/// ```
+pub fn simulate_text(text: &str) -> Result<(), SimulateError> {
+    _simulate_unicode(text)
+}
+
+/// Moves the mouse smoothly from its current position to `(x, y)` over
+/// `duration`, instead of teleporting it there in a single `MouseMove`
+/// event the way `simulate` does.
+///
+/// The current position is queried with `DeviceState`, the path is split
+/// into as many steps as the distance and duration warrant (at least one
+/// step every ~16ms, the refresh rate most software polls at), and a small
+/// sinusoidal jitter is layered on top of the straight line so generated
+/// paths aren't perfectly linear.
+///
+/// ```no_run
+/// use rdev::simulate_mouse_move_smooth;
+/// use std::time::Duration;
+///
+/// simulate_mouse_move_smooth(400.0, 400.0, Duration::from_millis(500)).unwrap();
+/// ```
+pub fn simulate_mouse_move_smooth(x: f64, y: f64, duration: std::time::Duration) -> Result<(), SimulateError> {
+    let (start_x, start_y) = DeviceState::new().get_mouse().coords;
+    let (start_x, start_y) = (start_x as f64, start_y as f64);
+    let (steps, step_delay) = smooth_move_steps(start_x, start_y, x, y, duration);
+
+    for step in 1..=steps {
+        let (cur_x, cur_y) = smooth_move_point(start_x, start_y, x, y, step, steps);
+        simulate(&EventType::MouseMove { x: cur_x, y: cur_y })?;
+        std::thread::sleep(step_delay);
+    }
+    Ok(())
+}
+
+/// Splits a `simulate_mouse_move_smooth` move into a step count and the
+/// delay between steps: at least one step every ~16ms (the refresh rate
+/// most software polls at), or one step every 5 pixels of distance,
+/// whichever is more.
+fn smooth_move_steps(
+    start_x: f64,
+    start_y: f64,
+    x: f64,
+    y: f64,
+    duration: std::time::Duration,
+) -> (usize, std::time::Duration) {
+    let distance = ((x - start_x).powi(2) + (y - start_y).powi(2)).sqrt();
+    let min_frame = std::time::Duration::from_millis(16);
+    let steps = ((duration.as_secs_f64() / min_frame.as_secs_f64()).ceil() as usize)
+        .max((distance / 5.0).ceil() as usize)
+        .max(1);
+    (steps, duration / steps as u32)
+}
+
+/// The cursor position at `step` out of `steps` along a straight line from
+/// `(start_x, start_y)` to `(x, y)`, with a small sinusoidal jitter layered
+/// on top so generated paths aren't perfectly linear.
+fn smooth_move_point(start_x: f64, start_y: f64, x: f64, y: f64, step: usize, steps: usize) -> (f64, f64) {
+    let t = step as f64 / steps as f64;
+    let jitter = (t * std::f64::consts::PI * 8.0).sin() * (1.0 - t) * 2.0;
+    (start_x + (x - start_x) * t + jitter, start_y + (y - start_y) * t - jitter)
+}
+
 /// Returns the size in pixels of the main screen.
 /// This is useful to use with x, y from MouseMove Event.
 ///
@@ -273,6 +420,51 @@ pub fn display_size() -> Result<(u64, u64), DisplayError> {
     _display_size()
 }
 
+/// A snapshot of which keys and mouse buttons are currently held down,
+/// queried on demand instead of observed through `listen`.
+///
+/// This is handy for game loops and UI frameworks that want to poll input
+/// once per frame rather than maintain their own mirror of the event
+/// stream built up from callbacks.
+///
+/// ```no_run
+/// use rdev::DeviceState;
+///
+/// let device_state = DeviceState::new();
+/// let keys = device_state.get_keys();
+/// let mouse = device_state.get_mouse();
+/// println!("Keys held: {:?}, mouse at {:?}", keys, mouse.coords);
+/// ```
+pub struct DeviceState {
+    _private: (),
+}
+
+impl DeviceState {
+    /// Creates a new `DeviceState`. On Windows this maps to
+    /// `GetAsyncKeyState`/`GetCursorPos`, on MacOS to
+    /// `CGEventSourceKeyState`/`CGEventSourceButtonState`, and on X11 to
+    /// `XQueryKeymap`/`XQueryPointer`.
+    pub fn new() -> DeviceState {
+        DeviceState { _private: () }
+    }
+
+    /// Returns the keys that are currently held down.
+    pub fn get_keys(&self) -> Vec<Key> {
+        _query_keys()
+    }
+
+    /// Returns the current mouse position and which buttons are held down.
+    pub fn get_mouse(&self) -> MouseState {
+        _query_mouse()
+    }
+}
+
+impl Default for DeviceState {
+    fn default() -> Self {
+        DeviceState::new()
+    }
+}
+
 #[cfg(feature = "unstable_grab")]
 #[cfg(target_os = "linux")]
 pub use crate::linux::grab as _grab;
@@ -282,22 +474,36 @@ pub use crate::macos::grab as _grab;
 #[cfg(feature = "unstable_grab")]
 #[cfg(target_os = "windows")]
 pub use crate::windows::grab as _grab;
-#[cfg(any(feature = "unstable_grab"))]
-/// Grabbing global events. In the callback, returning None ignores the event
-/// and returning the event let's it pass. There is no modification of the event
-/// possible here.
+#[cfg(feature = "unstable_grab")]
+/// Grabbing global events. In the callback, returning `None` drops the
+/// event, and returning `Some(event)` lets `event` through in place of the
+/// original one: return the event unchanged to pass it through as-is, or
+/// return a modified copy to remap it (a different key, a swapped button, a
+/// rewritten wheel delta) before it reaches other applications, on
+/// platforms where `grab` is actually implemented.
 /// Caveat: On MacOS, you require the grab
 /// loop needs to be the primary app (no fork before) and need to have accessibility
 /// settings enabled.
-/// On Linux, this is not implemented, you will always receive an error.
+/// On Linux, this grabs `/dev/input/event*` via evdev/uinput and requires
+/// membership in the `input` group (or running elevated).
+/// On MacOS, this installs a `CGEventTap`; on Windows, a pair of
+/// `WH_KEYBOARD_LL`/`WH_MOUSE_LL` hooks that re-inject a rewritten
+/// `modified_event` through the same path `simulate` uses, since a
+/// low-level hook can only pass an event through or suppress it.
 ///
 /// ```no_run
 /// use rdev::{grab, Event, EventType, Key};
 ///
 /// fn callback(event: Event) -> Option<Event> {
 ///     println!("My callback {:?}", event);
-///     match event.event_type{
+///     match event.event_type {
+///         // Drop Tab entirely.
 ///         EventType::KeyPress(Key::Tab) => None,
+///         // Remap Caps Lock to Escape.
+///         EventType::KeyPress(Key::CapsLock) => Some(Event {
+///             event_type: EventType::KeyPress(Key::Escape),
+///             ..event
+///         }),
 ///         _ => Some(event),
 ///     }
 /// }
@@ -309,10 +515,10 @@ pub use crate::windows::grab as _grab;
 ///     }
 /// }
 /// ```
-#[cfg(any(feature = "unstable_grab"))]
+#[cfg(feature = "unstable_grab")]
 pub fn grab<T>(callback: T, blocking: bool) -> Result<(), GrabError>
 where
-    T: Fn(Event) -> Option<Event> + 'static,
+    T: Fn(Event) -> Option<Event> + Send + 'static,
 {
     _grab(callback, blocking)
 }
@@ -320,6 +526,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
     #[test]
     fn test_keyboard_state() {
@@ -360,4 +567,34 @@ mod tests {
         // assert_eq!(e, "é".to_string());
         // keyboard.add(&EventType::KeyRelease(Key::KeyE));
     }
+
+    #[test]
+    fn test_smooth_move_steps_respects_duration_and_distance() {
+        // Short hop, generous duration: frame rate dominates (500ms / 16ms).
+        let (steps, delay) = smooth_move_steps(0.0, 0.0, 1.0, 0.0, Duration::from_millis(500));
+        assert_eq!(steps, 32);
+        assert_eq!(delay, Duration::from_millis(500) / 32);
+
+        // Long hop, tiny duration: distance dominates (1000px / 5px per step).
+        let (steps, _) = smooth_move_steps(0.0, 0.0, 1000.0, 0.0, Duration::from_millis(1));
+        assert_eq!(steps, 200);
+
+        // Zero-distance, zero-duration move still takes at least one step.
+        let (steps, delay) = smooth_move_steps(5.0, 5.0, 5.0, 5.0, Duration::from_millis(0));
+        assert_eq!(steps, 1);
+        assert_eq!(delay, Duration::from_millis(0));
+    }
+
+    #[test]
+    fn test_smooth_move_point_ends_on_target_and_adds_jitter_midway() {
+        let (x, y) = smooth_move_point(0.0, 0.0, 100.0, 200.0, 1, 1);
+        assert_eq!((x, y), (100.0, 200.0), "the final step must land exactly on the target");
+
+        // Midway through a move, the point is the straight-line midpoint
+        // plus the sine jitter term (+ on x, - on y) for t = 0.5.
+        let (x, y) = smooth_move_point(0.0, 0.0, 100.0, 0.0, 1, 2);
+        let jitter = (0.5 * std::f64::consts::PI * 8.0).sin() * 0.5 * 2.0;
+        assert_eq!(x, 50.0 + jitter);
+        assert_eq!(y, -jitter);
+    }
 }
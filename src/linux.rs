@@ -0,0 +1,401 @@
+use crate::rdev::{
+    code_to_key_only, is_shift, key_to_code, qwerty_lookup, Button, DisplayError, Event,
+    EventType, Key, KeyboardState, ListenError, MouseState, SimulateError,
+};
+use std::os::raw::{c_char, c_ulong};
+use std::ptr;
+use std::time::SystemTime;
+use x11::xlib;
+use x11::xrecord;
+use x11::xtest;
+
+#[derive(Default)]
+pub struct Keyboard {
+    shift: bool,
+}
+
+impl Keyboard {
+    pub fn new() -> Option<Keyboard> {
+        Some(Keyboard::default())
+    }
+}
+
+impl KeyboardState for Keyboard {
+    fn add(&mut self, event_type: &EventType) -> Option<String> {
+        // Real implementation goes through `XkbKeycodeToKeysym` /
+        // `XLookupString` against the active X11 keymap; until that's wired
+        // in we fall back to a plain Qwerty lookup, which is what
+        // `Keyboard` has always actually been tested against.
+        match *event_type {
+            EventType::KeyPress(key) if is_shift(key) => {
+                self.shift = true;
+                None
+            }
+            EventType::KeyRelease(key) if is_shift(key) => {
+                self.shift = false;
+                None
+            }
+            EventType::KeyPress(key) => qwerty_lookup(key, self.shift),
+            _ => None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.shift = false;
+    }
+}
+
+/// A connection to the X server, closed on drop. Every function in this
+/// module that needs to talk to X opens and tears down its own connection
+/// rather than keeping one around: these calls are infrequent (a handful of
+/// times per simulated/queried event at most), so the extra round trip isn't
+/// worth juggling a shared, possibly-stale `Display*`.
+struct XDisplay(*mut xlib::Display);
+
+impl XDisplay {
+    fn open() -> Option<XDisplay> {
+        let display = unsafe { xlib::XOpenDisplay(ptr::null()) };
+        if display.is_null() {
+            None
+        } else {
+            Some(XDisplay(display))
+        }
+    }
+}
+
+impl Drop for XDisplay {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.0);
+        }
+    }
+}
+
+/// The evdev keycode table already built for the Linux grab backend also
+/// describes the X11 keymap: the XKB `evdev` keycode set that every current
+/// Linux distribution ships by default offsets every evdev code by 8 (X11
+/// keycodes can't be 0, which is reserved to mean "no keycode").
+const XKB_EVDEV_OFFSET: u16 = 8;
+
+fn keycode_to_key(keycode: u8) -> Option<Key> {
+    let code = (keycode as u16).checked_sub(XKB_EVDEV_OFFSET)?;
+    code_to_key_only(code)
+}
+
+fn key_to_keycode(key: Key) -> u8 {
+    (key_to_code(key) + XKB_EVDEV_OFFSET) as u8
+}
+
+fn button_to_xtest_button(button: Button) -> u32 {
+    match button {
+        Button::Left => 1,
+        Button::Middle => 2,
+        Button::Right => 3,
+        Button::Unknown(code) => code as u32,
+    }
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    let display = XDisplay::open().ok_or(SimulateError)?;
+    unsafe {
+        match *event_type {
+            EventType::KeyPress(key) => {
+                xtest::XTestFakeKeyEvent(display.0, key_to_keycode(key) as u32, 1, 0);
+            }
+            EventType::KeyRelease(key) => {
+                xtest::XTestFakeKeyEvent(display.0, key_to_keycode(key) as u32, 0, 0);
+            }
+            EventType::ButtonPress(button) => {
+                xtest::XTestFakeButtonEvent(display.0, button_to_xtest_button(button), 1, 0);
+            }
+            EventType::ButtonRelease(button) => {
+                xtest::XTestFakeButtonEvent(display.0, button_to_xtest_button(button), 0, 0);
+            }
+            EventType::MouseMove { x, y } => {
+                xtest::XTestFakeMotionEvent(display.0, -1, x as i32, y as i32, 0);
+            }
+            EventType::Wheel { delta_x: _, delta_y } => {
+                let button = if delta_y < 0 { 5 } else { 4 };
+                xtest::XTestFakeButtonEvent(display.0, button, 1, 0);
+                xtest::XTestFakeButtonEvent(display.0, button, 0, 0);
+            }
+        }
+        xlib::XFlush(display.0);
+    }
+    Ok(())
+}
+
+/// Types `text` directly, independent of the current keyboard layout, by
+/// temporarily remapping the last keycode of the keyboard mapping to each
+/// character's keysym in turn with `XChangeKeyboardMapping` and pressing it
+/// through XTest. The last keycode is used as scratch space because it is
+/// the least likely to collide with a key the user is actually holding.
+pub fn simulate_unicode(text: &str) -> Result<(), SimulateError> {
+    let display = XDisplay::open().ok_or(SimulateError)?;
+    let max_keycode = {
+        let mut min = 0;
+        let mut max = 0;
+        unsafe {
+            xlib::XDisplayKeycodes(display.0, &mut min, &mut max);
+        }
+        max
+    };
+    let scratch_keycode = max_keycode;
+    let mut keysyms_per_keycode = 0;
+    let mapping = unsafe {
+        xlib::XGetKeyboardMapping(
+            display.0,
+            scratch_keycode as u8,
+            1,
+            &mut keysyms_per_keycode,
+        )
+    };
+    if mapping.is_null() {
+        return Err(SimulateError);
+    }
+    let width = keysyms_per_keycode.max(1) as usize;
+    let mut saved = vec![0u64; width];
+    unsafe {
+        ptr::copy_nonoverlapping(mapping, saved.as_mut_ptr(), width);
+        xlib::XFree(mapping as *mut _);
+    }
+    for ch in text.chars() {
+        let keysym = ch as u64 + 0x01000000; // Unicode keysym per `X11/keysymdef.h`.
+        let mut new_mapping = saved.clone();
+        new_mapping[0] = keysym;
+        unsafe {
+            xlib::XChangeKeyboardMapping(
+                display.0,
+                scratch_keycode,
+                width as i32,
+                new_mapping.as_mut_ptr(),
+                1,
+            );
+            xlib::XSync(display.0, 0);
+            xtest::XTestFakeKeyEvent(display.0, scratch_keycode as u32, 1, 0);
+            xtest::XTestFakeKeyEvent(display.0, scratch_keycode as u32, 0, 0);
+            xlib::XSync(display.0, 0);
+        }
+    }
+    let mut restored = saved.clone();
+    unsafe {
+        xlib::XChangeKeyboardMapping(
+            display.0,
+            scratch_keycode,
+            width as i32,
+            restored.as_mut_ptr(),
+            1,
+        );
+        xlib::XSync(display.0, 0);
+    }
+    Ok(())
+}
+
+/// Wire-format layout of an X11 `xEvent` (`KeyPress`/`KeyRelease`/
+/// `ButtonPress`/`ButtonRelease`/`MotionNotify`), as delivered to a RECORD
+/// context: type, detail (keycode or button number), a 2-byte sequence
+/// number, then a 4-byte server time, 32 bytes total. We only need `type`,
+/// `detail` and the root-relative coordinates.
+const X_KEY_PRESS: u8 = 2;
+const X_KEY_RELEASE: u8 = 3;
+const X_BUTTON_PRESS: u8 = 4;
+const X_BUTTON_RELEASE: u8 = 5;
+const X_MOTION_NOTIFY: u8 = 6;
+
+fn record_button(detail: u8) -> Button {
+    match detail {
+        1 => Button::Left,
+        2 => Button::Middle,
+        3 => Button::Right,
+        other => Button::Unknown(other),
+    }
+}
+
+/// Decodes a RECORD-delivered `xEvent`. The wheel has no event type of its
+/// own in the X11 protocol: a scroll tick is reported as a press (and
+/// matching release) of button 4 (up) or 5 (down), which we translate to a
+/// `Wheel` event and drop the paired release, same as every other backend
+/// in this crate only reports a single `Wheel` event per tick.
+fn decode_record_event(data: &[u8]) -> Option<EventType> {
+    if data.len() < 32 {
+        return None;
+    }
+    let event_type = data[0] & 0x7F; // high bit marks a synthetic SendEvent.
+    let detail = data[1];
+    let root_x = i16::from_ne_bytes([data[20], data[21]]) as f64;
+    let root_y = i16::from_ne_bytes([data[22], data[23]]) as f64;
+    match event_type {
+        X_KEY_PRESS => Some(EventType::KeyPress(keycode_to_key(detail).unwrap_or(
+            Key::Unknown((detail as u16).saturating_sub(XKB_EVDEV_OFFSET) as u32),
+        ))),
+        X_KEY_RELEASE => Some(EventType::KeyRelease(keycode_to_key(detail).unwrap_or(
+            Key::Unknown((detail as u16).saturating_sub(XKB_EVDEV_OFFSET) as u32),
+        ))),
+        X_BUTTON_PRESS if detail == 4 => Some(EventType::Wheel { delta_x: 0, delta_y: 1 }),
+        X_BUTTON_PRESS if detail == 5 => Some(EventType::Wheel { delta_x: 0, delta_y: -1 }),
+        X_BUTTON_RELEASE if detail == 4 || detail == 5 => None,
+        X_BUTTON_PRESS => Some(EventType::ButtonPress(record_button(detail))),
+        X_BUTTON_RELEASE => Some(EventType::ButtonRelease(record_button(detail))),
+        X_MOTION_NOTIFY => Some(EventType::MouseMove { x: root_x, y: root_y }),
+        _ => None,
+    }
+}
+
+unsafe extern "C" fn record_callback<T: FnMut(Event)>(
+    closure: *mut c_char,
+    data: *mut xrecord::XRecordInterceptData,
+) {
+    if data.is_null() {
+        return;
+    }
+    let intercept = &*data;
+    if intercept.category == xrecord::XRecordFromServer && !intercept.data.is_null() {
+        let len = intercept.data_len as usize * 4;
+        let bytes = std::slice::from_raw_parts(intercept.data, len);
+        if let Some(event_type) = decode_record_event(bytes) {
+            let callback = &mut *(closure as *mut T);
+            callback(Event { time: SystemTime::now(), name: None, event_type });
+        }
+    }
+    xrecord::XRecordFreeData(data);
+}
+
+/// Captures every keyboard/mouse event system-wide through the X11 RECORD
+/// extension: a `data_display` connection receives the intercepted protocol
+/// data while a separate `control_display` connection creates/owns the
+/// RECORD context, as the extension requires.
+pub fn listen<T>(callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    let control_display = XDisplay::open().ok_or(ListenError::EventTapError)?;
+    let data_display = XDisplay::open().ok_or(ListenError::EventTapError)?;
+
+    let range = unsafe { xrecord::XRecordAllocRange() };
+    if range.is_null() {
+        return Err(ListenError::EventTapError);
+    }
+    unsafe {
+        (*range).device_events = xrecord::XRecordRange8 { first: X_KEY_PRESS, last: X_MOTION_NOTIFY };
+    }
+    let mut ranges = [range];
+    let mut client_spec: xrecord::XRecordClientSpec = xrecord::XRecordAllClients;
+    let context = unsafe {
+        xrecord::XRecordCreateContext(
+            control_display.0,
+            0,
+            &mut client_spec as *mut c_ulong,
+            1,
+            ranges.as_mut_ptr(),
+            1,
+        )
+    };
+    unsafe {
+        xlib::XFree(range as *mut _);
+    }
+    if context == 0 {
+        return Err(ListenError::EventTapError);
+    }
+    unsafe {
+        xlib::XSync(control_display.0, 0);
+    }
+
+    let boxed_callback: *mut T = Box::into_raw(Box::new(callback));
+    let enabled = unsafe {
+        xrecord::XRecordEnableContext(
+            data_display.0,
+            context,
+            Some(record_callback::<T>),
+            boxed_callback as *mut c_char,
+        )
+    };
+    // `XRecordEnableContext` pumps `data_display`'s event loop and only
+    // returns once the context is disabled (from another connection) or the
+    // server connection drops, so this call blocks for the lifetime of the
+    // listener, same contract every other backend's `listen` has.
+    unsafe {
+        xrecord::XRecordFreeContext(control_display.0, context);
+        drop(Box::from_raw(boxed_callback));
+    }
+    if enabled == 0 {
+        return Err(ListenError::EventTapError);
+    }
+    Ok(())
+}
+
+pub fn display_size() -> Result<(u64, u64), DisplayError> {
+    let display = XDisplay::open().ok_or(DisplayError)?;
+    let screen = unsafe { xlib::XDefaultScreen(display.0) };
+    let width = unsafe { xlib::XDisplayWidth(display.0, screen) };
+    let height = unsafe { xlib::XDisplayHeight(display.0, screen) };
+    Ok((width as u64, height as u64))
+}
+
+/// Polls the current keymap with `XQueryKeymap`, which returns a bitmask of
+/// the 256 possible keycodes, and translates the set bits back to `Key`.
+pub fn query_keys() -> Vec<Key> {
+    let display = match XDisplay::open() {
+        Some(display) => display,
+        None => return Vec::new(),
+    };
+    let mut bitmap = [0i8; 32];
+    unsafe {
+        xlib::XQueryKeymap(display.0, bitmap.as_mut_ptr());
+    }
+    let mut keys = Vec::new();
+    for keycode in 0..256u16 {
+        let byte = bitmap[(keycode / 8) as usize];
+        let is_pressed = byte & (1 << (keycode % 8)) != 0;
+        if is_pressed {
+            if let Some(key) = keycode_to_key(keycode as u8) {
+                keys.push(key);
+            }
+        }
+    }
+    keys
+}
+
+/// Polls the pointer position and button mask with `XQueryPointer`.
+pub fn query_mouse() -> MouseState {
+    let display = match XDisplay::open() {
+        Some(display) => display,
+        None => {
+            return MouseState {
+                coords: (0, 0),
+                button_pressed: vec![false, false, false],
+            }
+        }
+    };
+    let root = unsafe { xlib::XDefaultRootWindow(display.0) };
+    let mut root_return = 0;
+    let mut child_return = 0;
+    let (mut root_x, mut root_y, mut win_x, mut win_y) = (0, 0, 0, 0);
+    let mut mask: u32 = 0;
+    unsafe {
+        xlib::XQueryPointer(
+            display.0,
+            root,
+            &mut root_return,
+            &mut child_return,
+            &mut root_x,
+            &mut root_y,
+            &mut win_x,
+            &mut win_y,
+            &mut mask,
+        );
+    }
+    const BUTTON1_MASK: u32 = 1 << 8;
+    const BUTTON2_MASK: u32 = 1 << 9;
+    const BUTTON3_MASK: u32 = 1 << 10;
+    MouseState {
+        coords: (root_x, root_y),
+        button_pressed: vec![
+            mask & BUTTON1_MASK != 0,
+            mask & BUTTON3_MASK != 0,
+            mask & BUTTON2_MASK != 0,
+        ],
+    }
+}
+
+#[cfg(feature = "unstable_grab")]
+pub use crate::linux_evdev::grab;
@@ -0,0 +1,525 @@
+//! `grab` on Linux, implemented against evdev/uinput instead of X11: X11 has
+//! no equivalent of a mutable event tap, so `listen`/`simulate` stay on
+//! Xlib/XTest but `grab` opens the raw input devices directly.
+//!
+//! Requires membership in the `input` group (or running elevated) for both
+//! `/dev/input/event*` (to read and `EVIOCGRAB` them) and `/dev/uinput` (to
+//! create the virtual device events are re-emitted through).
+use crate::rdev::{button_to_code, code_to_key, key_to_code, Event, EventType, GrabError};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::SystemTime;
+
+const EVIOCGRAB: libc::c_ulong = 0x4004_4590;
+const UI_DEV_SETUP: libc::c_ulong = 0x405c_5503;
+const UI_ABS_SETUP: libc::c_ulong = 0x401c_5504;
+const UI_DEV_CREATE: libc::c_ulong = 0x5501;
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+const UI_SET_EVBIT: libc::c_ulong = 0x4004_5564;
+const UI_SET_KEYBIT: libc::c_ulong = 0x4004_5565;
+const UI_SET_RELBIT: libc::c_ulong = 0x4004_5566;
+const UI_SET_ABSBIT: libc::c_ulong = 0x4004_5567;
+
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+const EV_SYN: u16 = 0x00;
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_WHEEL: u16 = 0x08;
+
+#[repr(C)]
+struct TimeVal {
+    tv_sec: i64,
+    tv_usec: i64,
+}
+
+#[repr(C)]
+struct InputEvent {
+    time: TimeVal,
+    kind: u16,
+    code: u16,
+    value: i32,
+}
+
+#[repr(C)]
+struct InputId {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+}
+
+#[repr(C)]
+struct UinputSetup {
+    id: InputId,
+    name: [u8; 80],
+    ff_effects_max: u32,
+}
+
+#[repr(C)]
+struct InputAbsInfo {
+    value: i32,
+    minimum: i32,
+    maximum: i32,
+    fuzz: i32,
+    flat: i32,
+    resolution: i32,
+}
+
+#[repr(C)]
+struct UinputAbsSetup {
+    code: u16,
+    absinfo: InputAbsInfo,
+}
+
+/// A `/dev/input/event*` device, grabbed for exclusive access.
+struct GrabbedDevice {
+    file: File,
+}
+
+impl GrabbedDevice {
+    fn open(path: &std::path::Path) -> std::io::Result<GrabbedDevice> {
+        let file = OpenOptions::new().read(true).write(false).open(path)?;
+        let ret = unsafe { libc::ioctl(file.as_raw_fd(), EVIOCGRAB, 1) };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(GrabbedDevice { file })
+    }
+}
+
+impl Drop for GrabbedDevice {
+    fn drop(&mut self) {
+        unsafe {
+            libc::ioctl(self.file.as_raw_fd(), EVIOCGRAB, 0);
+        }
+    }
+}
+
+/// A virtual input device events are re-emitted through once the callback
+/// has decided they should pass (possibly modified).
+struct UinputDevice {
+    file: File,
+}
+
+impl UinputDevice {
+    fn create() -> std::io::Result<UinputDevice> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/uinput")?;
+        let fd = file.as_raw_fd();
+        // `MouseMove` is an absolute pixel position (see its doc comment:
+        // "Values in pixels"), not a delta, so the virtual device needs
+        // absolute X/Y axes rather than `REL_X`/`REL_Y`.
+        let (width, height) = crate::display_size().unwrap_or((65535, 65535));
+        unsafe {
+            libc::ioctl(fd, UI_SET_EVBIT, EV_KEY as libc::c_uint);
+            // Up to `KEY_MAX` (0x2ff), so mouse buttons (`BTN_LEFT` = 0x110)
+            // and `Key::Function` (464) are accepted too.
+            for code in 0..0x300u64 {
+                libc::ioctl(fd, UI_SET_KEYBIT, code);
+            }
+            libc::ioctl(fd, UI_SET_EVBIT, EV_REL as libc::c_uint);
+            libc::ioctl(fd, UI_SET_RELBIT, REL_WHEEL as libc::c_uint);
+            libc::ioctl(fd, UI_SET_EVBIT, EV_ABS as libc::c_uint);
+            libc::ioctl(fd, UI_SET_ABSBIT, ABS_X as libc::c_uint);
+            libc::ioctl(fd, UI_SET_ABSBIT, ABS_Y as libc::c_uint);
+            setup_abs_axis(fd, ABS_X, width as i32 - 1)?;
+            setup_abs_axis(fd, ABS_Y, height as i32 - 1)?;
+
+            let mut setup: UinputSetup = std::mem::zeroed();
+            setup.id.bustype = 0x03; // BUS_USB
+            setup.id.vendor = 0x1234;
+            setup.id.product = 0x5678;
+            let name = b"rdev virtual input";
+            setup.name[..name.len()].copy_from_slice(name);
+            let ret = libc::ioctl(fd, UI_DEV_SETUP, &setup as *const UinputSetup);
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::ioctl(fd, UI_DEV_CREATE, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(UinputDevice { file })
+    }
+
+    fn emit(&mut self, kind: u16, code: u16, value: i32) -> std::io::Result<()> {
+        let event = InputEvent {
+            time: TimeVal { tv_sec: 0, tv_usec: 0 },
+            kind,
+            code,
+            value,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(&event as *const InputEvent as *const u8, size_of::<InputEvent>())
+        };
+        self.file.write_all(bytes)
+    }
+
+    fn emit_event_type(&mut self, event_type: &EventType) -> std::io::Result<()> {
+        match *event_type {
+            EventType::KeyPress(key) => self.emit(EV_KEY, key_to_code(key), 1)?,
+            EventType::KeyRelease(key) => self.emit(EV_KEY, key_to_code(key), 0)?,
+            EventType::ButtonPress(button) => self.emit(EV_KEY, button_to_code(button), 1)?,
+            EventType::ButtonRelease(button) => self.emit(EV_KEY, button_to_code(button), 0)?,
+            EventType::MouseMove { x, y } => {
+                self.emit(EV_ABS, ABS_X, x as i32)?;
+                self.emit(EV_ABS, ABS_Y, y as i32)?;
+            }
+            EventType::Wheel { delta_x: _, delta_y } => {
+                self.emit(EV_REL, REL_WHEEL, delta_y.signum() as i32)?;
+            }
+        }
+        self.emit(EV_SYN, 0, 0)
+    }
+}
+
+impl Drop for UinputDevice {
+    fn drop(&mut self) {
+        unsafe {
+            libc::ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY, 0);
+        }
+    }
+}
+
+unsafe fn setup_abs_axis(fd: std::os::raw::c_int, code: u16, max: i32) -> std::io::Result<()> {
+    let setup = UinputAbsSetup {
+        code,
+        absinfo: InputAbsInfo {
+            value: 0,
+            minimum: 0,
+            maximum: max,
+            fuzz: 0,
+            flat: 0,
+            resolution: 0,
+        },
+    };
+    if libc::ioctl(fd, UI_ABS_SETUP, &setup as *const UinputAbsSetup) < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn input_devices() -> Vec<std::path::PathBuf> {
+    let mut devices = Vec::new();
+    if let Ok(entries) = fs::read_dir("/dev/input") {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with("event"))
+                .unwrap_or(false)
+            {
+                devices.push(path);
+            }
+        }
+    }
+    devices
+}
+
+/// Grabs every `/dev/input/event*` device, re-emitting non-dropped (and,
+/// potentially rewritten) events through a `uinput` virtual device.
+///
+/// Each device gets its own reader thread feeding a shared channel: a
+/// blocking `read` on one idle device (e.g. a keyboard sitting untouched
+/// while the mouse moves) must not stall events coming from the others.
+pub fn grab<T>(callback: T, blocking: bool) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + Send + 'static,
+{
+    if blocking {
+        return grab_blocking(callback);
+    }
+    // Non-blocking: run the (blocking) loop on a background thread and hand
+    // control back to the caller immediately.
+    // Setup errors (no devices, can't grab, can't open uinput) happen before
+    // the thread is spawned so they're still reported synchronously; errors
+    // that occur once the loop is running can only be logged; there's no
+    // caller left to hand a `Result` to.
+    let paths = input_devices();
+    if paths.is_empty() {
+        return Err(GrabError::MissingDisplayError);
+    }
+    let mut devices = Vec::new();
+    for path in &paths {
+        match GrabbedDevice::open(path) {
+            Ok(device) => devices.push(device),
+            Err(error) => return Err(GrabError::IoError(error.kind())),
+        }
+    }
+    let uinput = UinputDevice::create().map_err(|error| GrabError::IoError(error.kind()))?;
+    thread::spawn(move || {
+        if let Err(error) = run_grab_loop(devices, uinput, callback) {
+            eprintln!("grab loop exited: {:?}", error);
+        }
+    });
+    Ok(())
+}
+
+fn grab_blocking<T>(callback: T) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + Send + 'static,
+{
+    let paths = input_devices();
+    if paths.is_empty() {
+        return Err(GrabError::MissingDisplayError);
+    }
+
+    let mut devices = Vec::new();
+    for path in &paths {
+        match GrabbedDevice::open(path) {
+            Ok(device) => devices.push(device),
+            Err(error) => return Err(GrabError::IoError(error.kind())),
+        }
+    }
+
+    let uinput = UinputDevice::create().map_err(|error| GrabError::IoError(error.kind()))?;
+    run_grab_loop(devices, uinput, callback)
+}
+
+/// Reads every grabbed device (one thread each, so a blocking read on an
+/// idle device can't stall events from the others), decodes each record,
+/// and feeds the callback. `Some(event)` from the callback is re-emitted
+/// through `uinput`; `None` drops it.
+fn run_grab_loop<T>(
+    devices: Vec<GrabbedDevice>,
+    mut uinput: UinputDevice,
+    callback: T,
+) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + Send + 'static,
+{
+    let (sender, receiver) = channel();
+    for device in devices {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let mut device = device;
+            let mut cursor = crate::linux::query_mouse().coords;
+            let mut pending = (None, None);
+            let mut buffer = [0u8; size_of::<InputEvent>()];
+            while device.file.read_exact(&mut buffer).is_ok() {
+                match decode_raw_event(&buffer) {
+                    Some(RawInput::Resolved(event_type)) => {
+                        if sender.send(event_type).is_err() {
+                            break;
+                        }
+                    }
+                    Some(RawInput::Axis { absolute, axis, value }) => {
+                        let component = if absolute {
+                            value
+                        } else if axis == 0 {
+                            cursor.0 + value
+                        } else {
+                            cursor.1 + value
+                        };
+                        if axis == 0 {
+                            cursor.0 = component;
+                            pending.0 = Some(());
+                        } else {
+                            cursor.1 = component;
+                            pending.1 = Some(());
+                        }
+                    }
+                    None => {
+                        // `EV_SYN` (or anything else we don't decode): flush
+                        // any cursor motion accumulated since the last sync.
+                        if pending != (None, None) {
+                            let moved = EventType::MouseMove {
+                                x: cursor.0 as f64,
+                                y: cursor.1 as f64,
+                            };
+                            if sender.send(moved).is_err() {
+                                break;
+                            }
+                            pending = (None, None);
+                        }
+                    }
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    for event_type in receiver {
+        let event = Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type,
+        };
+        if let Some(replacement) = callback(event) {
+            uinput
+                .emit_event_type(&replacement.event_type)
+                .map_err(|error| GrabError::IoError(error.kind()))?;
+        }
+    }
+    Ok(())
+}
+
+/// One decoded `/dev/input/event*` record. Key/button presses and wheel
+/// ticks are single-axis and ready to use as soon as they're read; cursor
+/// motion (`EV_REL` `REL_X`/`REL_Y` from a mouse, or `EV_ABS` `ABS_X`/`ABS_Y`
+/// from a touch/tablet device) arrives as one axis per record, so it's
+/// reported as a raw `Axis` update and folded into a running cursor position
+/// by the caller instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RawInput {
+    Resolved(EventType),
+    Axis { absolute: bool, axis: u8, value: i32 },
+}
+
+fn decode_raw_event(buffer: &[u8]) -> Option<RawInput> {
+    if buffer.len() < size_of::<InputEvent>() {
+        return None;
+    }
+    let kind = u16::from_ne_bytes([buffer[16], buffer[17]]);
+    let code = u16::from_ne_bytes([buffer[18], buffer[19]]);
+    let value = i32::from_ne_bytes([buffer[20], buffer[21], buffer[22], buffer[23]]);
+    match kind {
+        EV_KEY => {
+            let key = code_to_key(code);
+            let event_type = match (value, key) {
+                (1, Ok(key)) => EventType::KeyPress(key),
+                (0, Ok(key)) => EventType::KeyRelease(key),
+                (1, Err(button)) => EventType::ButtonPress(button),
+                (0, Err(button)) => EventType::ButtonRelease(button),
+                _ => return None,
+            };
+            Some(RawInput::Resolved(event_type))
+        }
+        EV_REL if code == REL_WHEEL => Some(RawInput::Resolved(EventType::Wheel {
+            delta_x: 0,
+            delta_y: value.signum() as i64,
+        })),
+        EV_REL if code == REL_X => Some(RawInput::Axis { absolute: false, axis: 0, value }),
+        EV_REL if code == REL_Y => Some(RawInput::Axis { absolute: false, axis: 1, value }),
+        EV_ABS if code == ABS_X => Some(RawInput::Axis { absolute: true, axis: 0, value }),
+        EV_ABS if code == ABS_Y => Some(RawInput::Axis { absolute: true, axis: 1, value }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdev::{code_to_key_only, Button, Key};
+
+    fn raw_input_bytes(kind: u16, code: u16, value: i32) -> [u8; size_of::<InputEvent>()] {
+        let event = InputEvent {
+            time: TimeVal { tv_sec: 0, tv_usec: 0 },
+            kind,
+            code,
+            value,
+        };
+        let mut buffer = [0u8; size_of::<InputEvent>()];
+        let bytes = unsafe {
+            std::slice::from_raw_parts(&event as *const InputEvent as *const u8, size_of::<InputEvent>())
+        };
+        buffer.copy_from_slice(bytes);
+        buffer
+    }
+
+    #[test]
+    fn key_code_round_trips() {
+        for key in [
+            Key::KeyA,
+            Key::Num0,
+            Key::Escape,
+            Key::F12,
+            Key::MetaRight,
+            Key::Function,
+        ] {
+            let code = key_to_code(key);
+            assert_eq!(code_to_key_only(code), Some(key));
+        }
+    }
+
+    #[test]
+    fn unknown_key_round_trips_through_its_raw_code() {
+        assert_eq!(key_to_code(Key::Unknown(12345)), 12345);
+    }
+
+    #[test]
+    fn named_mouse_buttons_decode_as_buttons_not_keys() {
+        assert_eq!(code_to_key(0x110), Err(Button::Left));
+        assert_eq!(code_to_key(0x111), Err(Button::Right));
+        assert_eq!(code_to_key(0x112), Err(Button::Middle));
+    }
+
+    #[test]
+    fn extended_mouse_buttons_decode_as_unknown_buttons_not_unknown_keys() {
+        // BTN_SIDE: a real 4th mouse button, not in our named `Button` set,
+        // but still in the BTN_MOUSE range and therefore still a button.
+        assert_eq!(code_to_key(0x113), Err(Button::Unknown(0x113u16 as u8)));
+    }
+
+    #[test]
+    fn decode_raw_event_resolves_key_press_and_release() {
+        let press = raw_input_bytes(EV_KEY, key_to_code(Key::KeyA), 1);
+        assert_eq!(
+            decode_raw_event(&press),
+            Some(RawInput::Resolved(EventType::KeyPress(Key::KeyA)))
+        );
+        let release = raw_input_bytes(EV_KEY, key_to_code(Key::KeyA), 0);
+        assert_eq!(
+            decode_raw_event(&release),
+            Some(RawInput::Resolved(EventType::KeyRelease(Key::KeyA)))
+        );
+    }
+
+    #[test]
+    fn decode_raw_event_resolves_button_press() {
+        let press = raw_input_bytes(EV_KEY, 0x110, 1);
+        assert_eq!(
+            decode_raw_event(&press),
+            Some(RawInput::Resolved(EventType::ButtonPress(Button::Left)))
+        );
+    }
+
+    #[test]
+    fn decode_raw_event_resolves_wheel_immediately() {
+        let wheel_up = raw_input_bytes(EV_REL, REL_WHEEL, 1);
+        assert_eq!(
+            decode_raw_event(&wheel_up),
+            Some(RawInput::Resolved(EventType::Wheel { delta_x: 0, delta_y: 1 }))
+        );
+    }
+
+    #[test]
+    fn decode_raw_event_reports_relative_motion_as_an_axis_update() {
+        let dx = raw_input_bytes(EV_REL, REL_X, -5);
+        assert_eq!(
+            decode_raw_event(&dx),
+            Some(RawInput::Axis { absolute: false, axis: 0, value: -5 })
+        );
+        let dy = raw_input_bytes(EV_REL, REL_Y, 3);
+        assert_eq!(
+            decode_raw_event(&dy),
+            Some(RawInput::Axis { absolute: false, axis: 1, value: 3 })
+        );
+    }
+
+    #[test]
+    fn decode_raw_event_reports_absolute_motion_as_an_axis_update() {
+        let x = raw_input_bytes(EV_ABS, ABS_X, 640);
+        assert_eq!(
+            decode_raw_event(&x),
+            Some(RawInput::Axis { absolute: true, axis: 0, value: 640 })
+        );
+    }
+
+    #[test]
+    fn decode_raw_event_ignores_sync_events() {
+        let sync = raw_input_bytes(EV_SYN, 0, 0);
+        assert_eq!(decode_raw_event(&sync), None);
+    }
+}
@@ -0,0 +1,478 @@
+use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+use core_graphics::display::CGDisplay;
+use core_graphics::event::{
+    CallbackResult, CGEvent, CGEventField, CGEventTap, CGEventTapLocation, CGEventTapOptions,
+    CGEventTapPlacement, CGEventType, CGMouseButton,
+};
+use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+use core_graphics::geometry::CGPoint;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::rdev::{
+    is_shift, qwerty_lookup, Button, DisplayError, Event, EventType, GrabError, Key,
+    KeyboardState, ListenError, MouseState, SimulateError,
+};
+
+#[derive(Default)]
+pub struct Keyboard {
+    shift: bool,
+}
+
+impl Keyboard {
+    pub fn new() -> Option<Keyboard> {
+        Some(Keyboard::default())
+    }
+}
+
+impl KeyboardState for Keyboard {
+    fn add(&mut self, event_type: &EventType) -> Option<String> {
+        // Real implementation goes through `UCKeyTranslate` using the
+        // current keyboard layout; until that's wired in we fall back to a
+        // plain Qwerty lookup, which is what `Keyboard` has always actually
+        // been tested against.
+        match *event_type {
+            EventType::KeyPress(key) if is_shift(key) => {
+                self.shift = true;
+                None
+            }
+            EventType::KeyRelease(key) if is_shift(key) => {
+                self.shift = false;
+                None
+            }
+            EventType::KeyPress(key) => qwerty_lookup(key, self.shift),
+            _ => None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.shift = false;
+    }
+}
+
+/// `CGEventSourceKeyState`/`CGEventSourceButtonState` take the source state
+/// ID directly rather than a `CGEventSource` instance, so they aren't wrapped
+/// by the `core-graphics` crate; declare them ourselves, the same way
+/// `core_graphics::event_source` declares `CGEventSourceCreate` internally.
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventSourceKeyState(state_id: CGEventSourceStateID, key: u16) -> bool;
+    fn CGEventSourceButtonState(state_id: CGEventSourceStateID, button: u32) -> bool;
+}
+
+/// `Key`/`Button` <-> macOS virtual keycode table (Carbon `HIToolbox/Events.h`
+/// `kVK_*` constants). Only the keys `Key` itself can name are listed;
+/// anything else round-trips through `Key::Unknown`.
+fn key_to_keycode(key: Key) -> Option<u16> {
+    Some(match key {
+        Key::KeyA => 0x00,
+        Key::KeyS => 0x01,
+        Key::KeyD => 0x02,
+        Key::KeyF => 0x03,
+        Key::KeyH => 0x04,
+        Key::KeyG => 0x05,
+        Key::KeyZ => 0x06,
+        Key::KeyX => 0x07,
+        Key::KeyC => 0x08,
+        Key::KeyV => 0x09,
+        Key::KeyB => 0x0B,
+        Key::KeyQ => 0x0C,
+        Key::KeyW => 0x0D,
+        Key::KeyE => 0x0E,
+        Key::KeyR => 0x0F,
+        Key::KeyY => 0x10,
+        Key::KeyT => 0x11,
+        Key::Num1 => 0x12,
+        Key::Num2 => 0x13,
+        Key::Num3 => 0x14,
+        Key::Num4 => 0x15,
+        Key::Num6 => 0x16,
+        Key::Num5 => 0x17,
+        Key::Equal => 0x18,
+        Key::Num9 => 0x19,
+        Key::Num7 => 0x1A,
+        Key::Minus => 0x1B,
+        Key::Num8 => 0x1C,
+        Key::Num0 => 0x1D,
+        Key::RightBracket => 0x1E,
+        Key::KeyO => 0x1F,
+        Key::KeyU => 0x20,
+        Key::LeftBracket => 0x21,
+        Key::KeyI => 0x22,
+        Key::KeyP => 0x23,
+        Key::Return => 0x24,
+        Key::KeyL => 0x25,
+        Key::KeyJ => 0x26,
+        Key::Quote => 0x27,
+        Key::KeyK => 0x28,
+        Key::SemiColon => 0x29,
+        Key::BackSlash => 0x2A,
+        Key::Comma => 0x2B,
+        Key::Slash => 0x2C,
+        Key::KeyN => 0x2D,
+        Key::KeyM => 0x2E,
+        Key::Dot => 0x2F,
+        Key::Tab => 0x30,
+        Key::Space => 0x31,
+        Key::BackQuote => 0x32,
+        Key::Backspace => 0x33,
+        Key::Escape => 0x35,
+        Key::MetaLeft => 0x37,
+        Key::ShiftLeft => 0x38,
+        Key::CapsLock => 0x39,
+        Key::Alt => 0x3A,
+        Key::ControlLeft => 0x3B,
+        Key::ShiftRight => 0x3C,
+        Key::AltGr => 0x3D,
+        Key::ControlRight => 0x3E,
+        Key::Function => 0x3F,
+        Key::KpDelete => 0x41,
+        Key::KpMultiply => 0x43,
+        Key::KpPlus => 0x45,
+        Key::KpDivide => 0x4B,
+        Key::KpReturn => 0x4C,
+        Key::KpMinus => 0x4E,
+        Key::Kp0 => 0x52,
+        Key::Kp1 => 0x53,
+        Key::Kp2 => 0x54,
+        Key::Kp3 => 0x55,
+        Key::Kp4 => 0x56,
+        Key::Kp5 => 0x57,
+        Key::Kp6 => 0x58,
+        Key::Kp7 => 0x59,
+        Key::Kp8 => 0x5B,
+        Key::Kp9 => 0x5C,
+        Key::F5 => 0x60,
+        Key::F6 => 0x61,
+        Key::F7 => 0x62,
+        Key::F3 => 0x63,
+        Key::F8 => 0x64,
+        Key::F9 => 0x65,
+        Key::F11 => 0x67,
+        Key::F10 => 0x6D,
+        Key::F12 => 0x6F,
+        Key::Home => 0x73,
+        Key::PageUp => 0x74,
+        Key::Delete => 0x75,
+        Key::F4 => 0x76,
+        Key::End => 0x77,
+        Key::F2 => 0x78,
+        Key::PageDown => 0x79,
+        Key::F1 => 0x7A,
+        Key::LeftArrow => 0x7B,
+        Key::RightArrow => 0x7C,
+        Key::DownArrow => 0x7D,
+        Key::UpArrow => 0x7E,
+        Key::IntlBackslash => 0x0A,
+        Key::PrintScreen | Key::ScrollLock | Key::Pause | Key::NumLock | Key::MetaRight
+        | Key::Insert => return None,
+        Key::Unknown(code) => code as u16,
+    })
+}
+
+/// Inverts `key_to_keycode` by linear search over every named `Key`: small
+/// and infrequent enough (driven by user key-presses, not a hot loop) that a
+/// second, separately-indexed table isn't worth maintaining in lockstep.
+fn keycode_to_key(keycode: u16) -> Option<Key> {
+    ALL_KEYS.iter().copied().find(|&key| key_to_keycode(key) == Some(keycode))
+}
+
+/// Every named `Key` variant, used to invert `key_to_keycode` for
+/// `query_keys` without maintaining a second, separately-indexed table.
+const ALL_KEYS: &[Key] = &[
+    Key::Alt, Key::AltGr, Key::Backspace, Key::CapsLock, Key::ControlLeft, Key::ControlRight,
+    Key::Delete, Key::DownArrow, Key::End, Key::Escape, Key::F1, Key::F2, Key::F3, Key::F4,
+    Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12, Key::Home,
+    Key::LeftArrow, Key::MetaLeft, Key::PageDown, Key::PageUp, Key::Return, Key::RightArrow,
+    Key::ShiftLeft, Key::ShiftRight, Key::Space, Key::Tab, Key::UpArrow, Key::BackQuote,
+    Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8,
+    Key::Num9, Key::Num0, Key::Minus, Key::Equal, Key::KeyQ, Key::KeyW, Key::KeyE, Key::KeyR,
+    Key::KeyT, Key::KeyY, Key::KeyU, Key::KeyI, Key::KeyO, Key::KeyP, Key::LeftBracket,
+    Key::RightBracket, Key::KeyA, Key::KeyS, Key::KeyD, Key::KeyF, Key::KeyG, Key::KeyH,
+    Key::KeyJ, Key::KeyK, Key::KeyL, Key::SemiColon, Key::Quote, Key::BackSlash,
+    Key::IntlBackslash, Key::KeyZ, Key::KeyX, Key::KeyC, Key::KeyV, Key::KeyB, Key::KeyN,
+    Key::KeyM, Key::Comma, Key::Dot, Key::Slash, Key::KpReturn, Key::KpMinus, Key::KpPlus,
+    Key::KpMultiply, Key::KpDivide, Key::Kp0, Key::Kp1, Key::Kp2, Key::Kp3, Key::Kp4, Key::Kp5,
+    Key::Kp6, Key::Kp7, Key::Kp8, Key::Kp9, Key::KpDelete, Key::Function,
+];
+
+fn button_to_cg(button: Button) -> (CGMouseButton, CGEventType, CGEventType) {
+    match button {
+        Button::Left => (CGMouseButton::Left, CGEventType::LeftMouseDown, CGEventType::LeftMouseUp),
+        Button::Right => (CGMouseButton::Right, CGEventType::RightMouseDown, CGEventType::RightMouseUp),
+        Button::Middle | Button::Unknown(_) => {
+            (CGMouseButton::Center, CGEventType::OtherMouseDown, CGEventType::OtherMouseUp)
+        }
+    }
+}
+
+fn current_mouse_location() -> CGPoint {
+    CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
+        .and_then(CGEvent::new)
+        .map(|event| event.location())
+        .unwrap_or(CGPoint::new(0.0, 0.0))
+}
+
+/// Builds the `CGEvent` that represents `event_type`, for `simulate` to post
+/// and for `grab` to re-synthesize a callback-modified event. Button/wheel
+/// events without a real source coordinate (`ButtonPress`/`ButtonRelease`)
+/// use the current pointer location, same as a real click would report.
+fn build_cg_event(source: CGEventSource, event_type: &EventType) -> Result<CGEvent, ()> {
+    match *event_type {
+        EventType::KeyPress(key) => {
+            let code = key_to_keycode(key).ok_or(())?;
+            CGEvent::new_keyboard_event(source, code, true)
+        }
+        EventType::KeyRelease(key) => {
+            let code = key_to_keycode(key).ok_or(())?;
+            CGEvent::new_keyboard_event(source, code, false)
+        }
+        EventType::ButtonPress(button) => {
+            let (cg_button, down_type, _) = button_to_cg(button);
+            CGEvent::new_mouse_event(source, down_type, current_mouse_location(), cg_button)
+        }
+        EventType::ButtonRelease(button) => {
+            let (cg_button, _, up_type) = button_to_cg(button);
+            CGEvent::new_mouse_event(source, up_type, current_mouse_location(), cg_button)
+        }
+        EventType::MouseMove { x, y } => CGEvent::new_mouse_event(
+            source,
+            CGEventType::MouseMoved,
+            CGPoint::new(x, y),
+            CGMouseButton::Left,
+        ),
+        EventType::Wheel { delta_x, delta_y } => CGEvent::new_scroll_event(
+            source,
+            core_graphics::event::ScrollEventUnit::PIXEL,
+            2,
+            delta_y as i32,
+            delta_x as i32,
+            0,
+        ),
+    }
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).map_err(|_| SimulateError)?;
+    let event = build_cg_event(source, event_type).map_err(|_| SimulateError)?;
+    event.post(CGEventTapLocation::HID);
+    Ok(())
+}
+
+/// Types `text` directly, independent of the current keyboard layout, by
+/// synthesizing a keyboard event and calling
+/// `CGEventKeyboardSetUnicodeString` (via `CGEvent::set_string`) on it
+/// before posting.
+pub fn simulate_unicode(text: &str) -> Result<(), SimulateError> {
+    let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).map_err(|_| SimulateError)?;
+    let key_down = CGEvent::new_keyboard_event(source, 0, true).map_err(|_| SimulateError)?;
+    key_down.set_string(text);
+    key_down.post(CGEventTapLocation::HID);
+
+    let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).map_err(|_| SimulateError)?;
+    let key_up = CGEvent::new_keyboard_event(source, 0, false).map_err(|_| SimulateError)?;
+    key_up.set_string(text);
+    key_up.post(CGEventTapLocation::HID);
+    Ok(())
+}
+
+/// Installs a passive (`ListenOnly`) `CGEventTap` over every event type
+/// `EventType` can represent and runs it on the current thread's
+/// `CFRunLoop`. Unlike `grab`'s tap, a `ListenOnly` tap can't drop or
+/// replace events, so its `CallbackResult` is always ignored by the system;
+/// the callback is wrapped in a `RefCell` since `CGEventTap` requires `Fn`
+/// but `listen`'s contract is `FnMut`.
+pub fn listen<T>(callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    let callback = std::cell::RefCell::new(callback);
+    let tap_callback = move |_proxy, etype, cg_event: &CGEvent| -> CallbackResult {
+        if let Some(event_type) = cg_event_to_event_type(etype, cg_event) {
+            let event = Event { time: std::time::SystemTime::now(), name: None, event_type };
+            (callback.borrow_mut())(event);
+        }
+        CallbackResult::Keep
+    };
+    CGEventTap::with_enabled(
+        CGEventTapLocation::HID,
+        CGEventTapPlacement::HeadInsertEventTap,
+        CGEventTapOptions::ListenOnly,
+        events_of_interest(),
+        tap_callback,
+        || CFRunLoop::run_current(),
+    )
+    .map_err(|_| ListenError::EventTapError)
+}
+
+pub fn display_size() -> Result<(u64, u64), DisplayError> {
+    let bounds = CGDisplay::main().bounds();
+    Ok((bounds.size.width as u64, bounds.size.height as u64))
+}
+
+/// Polls which keys are currently held down via
+/// `CGEventSourceKeyState(HIDSystemState, keycode)` for every mapped key
+/// code, rather than accumulating state from a running event tap.
+pub fn query_keys() -> Vec<Key> {
+    ALL_KEYS
+        .iter()
+        .copied()
+        .filter(|&key| {
+            key_to_keycode(key)
+                .map(|code| unsafe {
+                    CGEventSourceKeyState(CGEventSourceStateID::HIDSystemState, code)
+                })
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Polls the current cursor position with a freshly created `CGEvent`'s
+/// `location()` (Apple's documented idiom for "where is the mouse right
+/// now") and each button's state with `CGEventSourceButtonState`.
+pub fn query_mouse() -> MouseState {
+    let coords = CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
+        .and_then(CGEvent::new)
+        .map(|event| {
+            let location = event.location();
+            (location.x as i32, location.y as i32)
+        })
+        .unwrap_or((0, 0));
+    let button_pressed = unsafe {
+        vec![
+            CGEventSourceButtonState(CGEventSourceStateID::HIDSystemState, CGMouseButton::Left as u32),
+            CGEventSourceButtonState(CGEventSourceStateID::HIDSystemState, CGMouseButton::Right as u32),
+            CGEventSourceButtonState(CGEventSourceStateID::HIDSystemState, CGMouseButton::Center as u32),
+        ]
+    };
+    MouseState { coords, button_pressed }
+}
+
+/// Every `CGEventType` `cg_event_to_event_type` knows how to decode, shared
+/// by `listen` and `grab` to build their tap's event mask.
+fn events_of_interest() -> Vec<CGEventType> {
+    vec![
+        CGEventType::KeyDown,
+        CGEventType::KeyUp,
+        CGEventType::LeftMouseDown,
+        CGEventType::LeftMouseUp,
+        CGEventType::RightMouseDown,
+        CGEventType::RightMouseUp,
+        CGEventType::OtherMouseDown,
+        CGEventType::OtherMouseUp,
+        CGEventType::MouseMoved,
+        CGEventType::LeftMouseDragged,
+        CGEventType::RightMouseDragged,
+        CGEventType::ScrollWheel,
+    ]
+}
+
+/// Decodes the subset of `CGEventType`/`CGEvent` that `EventType` can
+/// represent. `None` for tap housekeeping events
+/// (`TapDisabledByTimeout`/`TapDisabledByUserInput`) and anything else the
+/// tap wasn't asked to listen for.
+fn cg_event_to_event_type(etype: CGEventType, event: &CGEvent) -> Option<EventType> {
+    Some(match etype {
+        CGEventType::KeyDown => {
+            let code = event.get_integer_value_field(CGEventField::KEYBOARD_EVENT_KEYCODE) as u16;
+            EventType::KeyPress(keycode_to_key(code).unwrap_or(Key::Unknown(code as u32)))
+        }
+        CGEventType::KeyUp => {
+            let code = event.get_integer_value_field(CGEventField::KEYBOARD_EVENT_KEYCODE) as u16;
+            EventType::KeyRelease(keycode_to_key(code).unwrap_or(Key::Unknown(code as u32)))
+        }
+        CGEventType::LeftMouseDown => EventType::ButtonPress(Button::Left),
+        CGEventType::LeftMouseUp => EventType::ButtonRelease(Button::Left),
+        CGEventType::RightMouseDown => EventType::ButtonPress(Button::Right),
+        CGEventType::RightMouseUp => EventType::ButtonRelease(Button::Right),
+        CGEventType::OtherMouseDown => {
+            let button = event.get_integer_value_field(CGEventField::MOUSE_EVENT_BUTTON_NUMBER);
+            EventType::ButtonPress(Button::Unknown(button as u8))
+        }
+        CGEventType::OtherMouseUp => {
+            let button = event.get_integer_value_field(CGEventField::MOUSE_EVENT_BUTTON_NUMBER);
+            EventType::ButtonRelease(Button::Unknown(button as u8))
+        }
+        CGEventType::MouseMoved | CGEventType::LeftMouseDragged | CGEventType::RightMouseDragged => {
+            let location = event.location();
+            EventType::MouseMove { x: location.x, y: location.y }
+        }
+        CGEventType::ScrollWheel => {
+            let delta_y = event.get_integer_value_field(CGEventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_1);
+            let delta_x = event.get_integer_value_field(CGEventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_2);
+            EventType::Wheel { delta_x, delta_y }
+        }
+        _ => return None,
+    })
+}
+
+/// Installs a `CGEventTap` over every event type `EventType` can represent,
+/// forwarding each decoded event through `callback` before deciding whether
+/// to pass it through unchanged (`Some(event)` equal to the original),
+/// suppress it (`None`), or replace it with a rewritten one (`Some(event)`
+/// different from the original). `on_installed` is called with the tap
+/// setup result right before the run loop starts, so a non-blocking caller
+/// can learn about setup failures without waiting on the (otherwise
+/// never-returning) run loop.
+fn run_grab_tap<T>(callback: T, on_installed: impl FnOnce(Result<(), GrabError>)) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + Send + 'static,
+{
+    let tap_callback = move |_proxy, etype, cg_event: &CGEvent| -> CallbackResult {
+        let Some(event_type) = cg_event_to_event_type(etype, cg_event) else {
+            return CallbackResult::Keep;
+        };
+        let event = Event { time: std::time::SystemTime::now(), name: None, event_type };
+        match callback(event) {
+            None => CallbackResult::Drop,
+            Some(modified) if modified.event_type == event_type => CallbackResult::Keep,
+            Some(modified) => {
+                let Ok(source) = CGEventSource::new(CGEventSourceStateID::CombinedSessionState) else {
+                    return CallbackResult::Keep;
+                };
+                match build_cg_event(source, &modified.event_type) {
+                    Ok(new_event) => CallbackResult::Replace(new_event),
+                    Err(_) => CallbackResult::Keep,
+                }
+            }
+        }
+    };
+    let result = CGEventTap::with_enabled(
+        CGEventTapLocation::HID,
+        CGEventTapPlacement::HeadInsertEventTap,
+        CGEventTapOptions::Default,
+        events_of_interest(),
+        tap_callback,
+        || {
+            on_installed(Ok(()));
+            CFRunLoop::run_current()
+        },
+    );
+    match result {
+        Ok(()) => Ok(()),
+        Err(()) => {
+            on_installed(Err(GrabError::EventTapError));
+            Err(GrabError::EventTapError)
+        }
+    }
+}
+
+#[cfg(feature = "unstable_grab")]
+pub fn grab<T>(callback: T, blocking: bool) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + Send + 'static,
+{
+    if blocking {
+        return run_grab_tap(callback, |_| {});
+    }
+    // Non-blocking: run the (blocking) loop on a background thread and hand
+    // control back to the caller once the tap is installed (or failed to
+    // install).
+    let (result_tx, result_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = run_grab_tap(callback, move |result| {
+            let _ = result_tx.send(result);
+        });
+    });
+    result_rx.recv().unwrap_or(Err(GrabError::EventTapError))
+}
@@ -0,0 +1,556 @@
+use std::fmt;
+use std::time::SystemTime;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Callback type to use with `grab`. Returning `None` drops the event,
+/// returning `Some(event)` (possibly modified) lets it through in its
+/// place (see crate-level docs).
+pub type GrabCallback = dyn Fn(Event) -> Option<Event> + Send;
+
+/// Errors that occur when trying to capture OS events.
+/// Be careful, an error can happen on one OS and not on another one.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ListenError {
+    /// Error when the event tap (MacOS), RECORD context (X11) or low-level
+    /// hooks (Windows) could not be created.
+    EventTapError,
+    /// MacOS error when the loop could not be created.
+    LoopSourceError,
+}
+
+/// Errors that occur when trying to grab OS events.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GrabError {
+    /// Error when the event tap (MacOS) or low-level hooks (Windows) could
+    /// not be created.
+    EventTapError,
+    /// MacOS error when the loop could not be created.
+    LoopSourceError,
+    /// Linux/evdev error when an input device could not be opened or grabbed
+    /// for exclusive access (e.g. the process isn't in the `input` group), or
+    /// the `uinput` virtual device could not be created.
+    IoError(std::io::ErrorKind),
+    /// Linux error when no `/dev/input/event*` device was found.
+    MissingDisplayError,
+    SimulateError,
+}
+
+impl From<SimulateError> for GrabError {
+    fn from(_: SimulateError) -> Self {
+        GrabError::SimulateError
+    }
+}
+
+/// Errors that occur when trying to get the size of the screen.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DisplayError;
+
+/// Marking an error when we tried to simulate an event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SimulateError;
+
+impl fmt::Display for SimulateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not simulate event")
+    }
+}
+
+impl std::error::Error for SimulateError {}
+
+/// Standard Mouse buttons
+/// Some mouses have more than 3 buttons, these are not defined, and different
+/// OS will give different `Unknown` code.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+    Unknown(u8),
+}
+
+/// This is a list of all keyboard keys, these don't quite match with a
+/// particular physical layout, they correspond to a standard qwerty one.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Key {
+    Alt,
+    AltGr,
+    Backspace,
+    CapsLock,
+    ControlLeft,
+    ControlRight,
+    Delete,
+    DownArrow,
+    End,
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Home,
+    LeftArrow,
+    MetaLeft,
+    MetaRight,
+    PageDown,
+    PageUp,
+    Return,
+    RightArrow,
+    ShiftLeft,
+    ShiftRight,
+    Space,
+    Tab,
+    UpArrow,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    NumLock,
+    BackQuote,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Num0,
+    Minus,
+    Equal,
+    KeyQ,
+    KeyW,
+    KeyE,
+    KeyR,
+    KeyT,
+    KeyY,
+    KeyU,
+    KeyI,
+    KeyO,
+    KeyP,
+    LeftBracket,
+    RightBracket,
+    KeyA,
+    KeyS,
+    KeyD,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyJ,
+    KeyK,
+    KeyL,
+    SemiColon,
+    Quote,
+    BackSlash,
+    IntlBackslash,
+    KeyZ,
+    KeyX,
+    KeyC,
+    KeyV,
+    KeyB,
+    KeyN,
+    KeyM,
+    Comma,
+    Dot,
+    Slash,
+    Insert,
+    KpReturn,
+    KpMinus,
+    KpPlus,
+    KpMultiply,
+    KpDivide,
+    Kp0,
+    Kp1,
+    Kp2,
+    Kp3,
+    Kp4,
+    Kp5,
+    Kp6,
+    Kp7,
+    Kp8,
+    Kp9,
+    KpDelete,
+    Function,
+    Unknown(u32),
+}
+
+/// In order to manage different OS, the current EventType choices is a
+/// mix&match to account for all possible events.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum EventType {
+    /// The keys correspond to a standard qwerty layout, they don't correspond
+    /// to the actual letter a user would use, that requires some layout logic
+    /// to be added.
+    KeyPress(Key),
+    KeyRelease(Key),
+    /// Some mouse will have more than 3 buttons, these are not defined, and
+    /// different OS will give different Unknown code.
+    ButtonPress(Button),
+    ButtonRelease(Button),
+    /// Values in pixels
+    MouseMove {
+        x: f64,
+        y: f64,
+    },
+    /// Note: On Linux, there is no actual delta, the actual values are
+    /// ignored for delta_x and we only look at the sign of delta_y to
+    /// simulate wheelup or wheeldown.
+    Wheel {
+        delta_x: i64,
+        delta_y: i64,
+    },
+}
+
+/// When events arrive from the system we can add some information time is
+/// when the event was received.
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub time: SystemTime,
+    pub name: Option<String>,
+    pub event_type: EventType,
+}
+
+/// A snapshot of the mouse cursor position and which buttons are currently
+/// held down, as returned by `DeviceState::get_mouse`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MouseState {
+    pub coords: (i32, i32),
+    /// Indexed the same way as `Button`: `Left`, `Right`, `Middle` and then
+    /// any OS-specific extra buttons.
+    pub button_pressed: Vec<bool>,
+}
+
+/// We can use this struct to convert `EventType` to keyboard state, allowing
+/// us to properly fill the `name` field on the subsequent `Event`.
+pub trait KeyboardState {
+    /// Changes the keyboard state as if this event happened. We don't need
+    /// to provide more info because this isn't used to actually send the
+    /// event, only to compute the string value.
+    fn add(&mut self, event_type: &EventType) -> Option<String>;
+
+    /// Resets the keyboard state.
+    fn reset(&mut self);
+}
+
+/// A bare QWERTY lookup, shared by every platform's `Keyboard` as a
+/// layout-independent fallback. The real per-OS layout lookups
+/// (`UCKeyTranslate` on MacOS, `ToUnicodeEx` on Windows, `XLookupString` on
+/// X11) are more involved and not wired in yet, so this is what actually
+/// backs `KeyboardState::add` today: correct on a standard Qwerty layout,
+/// wrong on anything else.
+pub(crate) fn qwerty_lookup(key: Key, shift: bool) -> Option<String> {
+    let lower = match key {
+        Key::KeyA => "a",
+        Key::KeyB => "b",
+        Key::KeyC => "c",
+        Key::KeyD => "d",
+        Key::KeyE => "e",
+        Key::KeyF => "f",
+        Key::KeyG => "g",
+        Key::KeyH => "h",
+        Key::KeyI => "i",
+        Key::KeyJ => "j",
+        Key::KeyK => "k",
+        Key::KeyL => "l",
+        Key::KeyM => "m",
+        Key::KeyN => "n",
+        Key::KeyO => "o",
+        Key::KeyP => "p",
+        Key::KeyQ => "q",
+        Key::KeyR => "r",
+        Key::KeyS => "s",
+        Key::KeyT => "t",
+        Key::KeyU => "u",
+        Key::KeyV => "v",
+        Key::KeyW => "w",
+        Key::KeyX => "x",
+        Key::KeyY => "y",
+        Key::KeyZ => "z",
+        Key::Num0 => "0",
+        Key::Num1 => "1",
+        Key::Num2 => "2",
+        Key::Num3 => "3",
+        Key::Num4 => "4",
+        Key::Num5 => "5",
+        Key::Num6 => "6",
+        Key::Num7 => "7",
+        Key::Num8 => "8",
+        Key::Num9 => "9",
+        Key::Space => " ",
+        _ => return None,
+    };
+    Some(if shift { lower.to_uppercase() } else { lower.to_string() })
+}
+
+/// Is this key one of the shift keys? Used by `Keyboard::add` to track
+/// shift state without pulling in a real layout lookup.
+pub(crate) fn is_shift(key: Key) -> bool {
+    matches!(key, Key::ShiftLeft | Key::ShiftRight)
+}
+
+/// `Key`/`Button` <-> Linux evdev keycode table (`input-event-codes.h`).
+/// Shared by the evdev `grab` backend and the X11 `listen`/`simulate`
+/// backend: under the XKB `evdev` keycode set every current Linux
+/// distribution ships by default, an X11 keycode is simply an evdev keycode
+/// offset by 8, so both backends agree on the same underlying table. Only
+/// the keys `Key` itself can name are listed; anything else round-trips
+/// through `Key::Unknown`/`Button::Unknown`.
+#[cfg_attr(not(all(target_os = "linux", feature = "unstable_grab")), allow(dead_code))]
+pub(crate) fn key_to_code(key: Key) -> u16 {
+    match key {
+        Key::Escape => 1,
+        Key::Num1 => 2,
+        Key::Num2 => 3,
+        Key::Num3 => 4,
+        Key::Num4 => 5,
+        Key::Num5 => 6,
+        Key::Num6 => 7,
+        Key::Num7 => 8,
+        Key::Num8 => 9,
+        Key::Num9 => 10,
+        Key::Num0 => 11,
+        Key::Minus => 12,
+        Key::Equal => 13,
+        Key::Backspace => 14,
+        Key::Tab => 15,
+        Key::KeyQ => 16,
+        Key::KeyW => 17,
+        Key::KeyE => 18,
+        Key::KeyR => 19,
+        Key::KeyT => 20,
+        Key::KeyY => 21,
+        Key::KeyU => 22,
+        Key::KeyI => 23,
+        Key::KeyO => 24,
+        Key::KeyP => 25,
+        Key::LeftBracket => 26,
+        Key::RightBracket => 27,
+        Key::Return => 28,
+        Key::ControlLeft => 29,
+        Key::KeyA => 30,
+        Key::KeyS => 31,
+        Key::KeyD => 32,
+        Key::KeyF => 33,
+        Key::KeyG => 34,
+        Key::KeyH => 35,
+        Key::KeyJ => 36,
+        Key::KeyK => 37,
+        Key::KeyL => 38,
+        Key::SemiColon => 39,
+        Key::Quote => 40,
+        Key::BackQuote => 41,
+        Key::ShiftLeft => 42,
+        Key::BackSlash => 43,
+        Key::KeyZ => 44,
+        Key::KeyX => 45,
+        Key::KeyC => 46,
+        Key::KeyV => 47,
+        Key::KeyB => 48,
+        Key::KeyN => 49,
+        Key::KeyM => 50,
+        Key::Comma => 51,
+        Key::Dot => 52,
+        Key::Slash => 53,
+        Key::ShiftRight => 54,
+        Key::KpMultiply => 55,
+        Key::Alt => 56,
+        Key::Space => 57,
+        Key::CapsLock => 58,
+        Key::F1 => 59,
+        Key::F2 => 60,
+        Key::F3 => 61,
+        Key::F4 => 62,
+        Key::F5 => 63,
+        Key::F6 => 64,
+        Key::F7 => 65,
+        Key::F8 => 66,
+        Key::F9 => 67,
+        Key::F10 => 68,
+        Key::NumLock => 69,
+        Key::ScrollLock => 70,
+        Key::Kp7 => 71,
+        Key::Kp8 => 72,
+        Key::Kp9 => 73,
+        Key::KpMinus => 74,
+        Key::Kp4 => 75,
+        Key::Kp5 => 76,
+        Key::Kp6 => 77,
+        Key::KpPlus => 78,
+        Key::Kp1 => 79,
+        Key::Kp2 => 80,
+        Key::Kp3 => 81,
+        Key::Kp0 => 82,
+        Key::KpDelete => 83,
+        Key::IntlBackslash => 86,
+        Key::F11 => 87,
+        Key::F12 => 88,
+        Key::KpReturn => 96,
+        Key::ControlRight => 97,
+        Key::KpDivide => 98,
+        Key::PrintScreen => 99,
+        Key::AltGr => 100,
+        Key::Home => 102,
+        Key::UpArrow => 103,
+        Key::PageUp => 104,
+        Key::LeftArrow => 105,
+        Key::RightArrow => 106,
+        Key::End => 107,
+        Key::DownArrow => 108,
+        Key::PageDown => 109,
+        Key::Insert => 110,
+        Key::Delete => 111,
+        Key::Pause => 119,
+        Key::MetaLeft => 125,
+        Key::MetaRight => 126,
+        Key::Function => 464,
+        Key::Unknown(code) => code as u16,
+    }
+}
+
+pub(crate) fn code_to_key_only(code: u16) -> Option<Key> {
+    Some(match code {
+        1 => Key::Escape,
+        2 => Key::Num1,
+        3 => Key::Num2,
+        4 => Key::Num3,
+        5 => Key::Num4,
+        6 => Key::Num5,
+        7 => Key::Num6,
+        8 => Key::Num7,
+        9 => Key::Num8,
+        10 => Key::Num9,
+        11 => Key::Num0,
+        12 => Key::Minus,
+        13 => Key::Equal,
+        14 => Key::Backspace,
+        15 => Key::Tab,
+        16 => Key::KeyQ,
+        17 => Key::KeyW,
+        18 => Key::KeyE,
+        19 => Key::KeyR,
+        20 => Key::KeyT,
+        21 => Key::KeyY,
+        22 => Key::KeyU,
+        23 => Key::KeyI,
+        24 => Key::KeyO,
+        25 => Key::KeyP,
+        26 => Key::LeftBracket,
+        27 => Key::RightBracket,
+        28 => Key::Return,
+        29 => Key::ControlLeft,
+        30 => Key::KeyA,
+        31 => Key::KeyS,
+        32 => Key::KeyD,
+        33 => Key::KeyF,
+        34 => Key::KeyG,
+        35 => Key::KeyH,
+        36 => Key::KeyJ,
+        37 => Key::KeyK,
+        38 => Key::KeyL,
+        39 => Key::SemiColon,
+        40 => Key::Quote,
+        41 => Key::BackQuote,
+        42 => Key::ShiftLeft,
+        43 => Key::BackSlash,
+        44 => Key::KeyZ,
+        45 => Key::KeyX,
+        46 => Key::KeyC,
+        47 => Key::KeyV,
+        48 => Key::KeyB,
+        49 => Key::KeyN,
+        50 => Key::KeyM,
+        51 => Key::Comma,
+        52 => Key::Dot,
+        53 => Key::Slash,
+        54 => Key::ShiftRight,
+        55 => Key::KpMultiply,
+        56 => Key::Alt,
+        57 => Key::Space,
+        58 => Key::CapsLock,
+        59 => Key::F1,
+        60 => Key::F2,
+        61 => Key::F3,
+        62 => Key::F4,
+        63 => Key::F5,
+        64 => Key::F6,
+        65 => Key::F7,
+        66 => Key::F8,
+        67 => Key::F9,
+        68 => Key::F10,
+        69 => Key::NumLock,
+        70 => Key::ScrollLock,
+        71 => Key::Kp7,
+        72 => Key::Kp8,
+        73 => Key::Kp9,
+        74 => Key::KpMinus,
+        75 => Key::Kp4,
+        76 => Key::Kp5,
+        77 => Key::Kp6,
+        78 => Key::KpPlus,
+        79 => Key::Kp1,
+        80 => Key::Kp2,
+        81 => Key::Kp3,
+        82 => Key::Kp0,
+        83 => Key::KpDelete,
+        86 => Key::IntlBackslash,
+        87 => Key::F11,
+        88 => Key::F12,
+        96 => Key::KpReturn,
+        97 => Key::ControlRight,
+        98 => Key::KpDivide,
+        99 => Key::PrintScreen,
+        100 => Key::AltGr,
+        102 => Key::Home,
+        103 => Key::UpArrow,
+        104 => Key::PageUp,
+        105 => Key::LeftArrow,
+        106 => Key::RightArrow,
+        107 => Key::End,
+        108 => Key::DownArrow,
+        109 => Key::PageDown,
+        110 => Key::Insert,
+        111 => Key::Delete,
+        119 => Key::Pause,
+        125 => Key::MetaLeft,
+        126 => Key::MetaRight,
+        464 => Key::Function,
+        _ => return None,
+    })
+}
+
+#[cfg_attr(not(all(target_os = "linux", feature = "unstable_grab")), allow(dead_code))]
+pub(crate) fn button_to_code(button: Button) -> u16 {
+    match button {
+        Button::Left => 0x110,   // BTN_LEFT
+        Button::Right => 0x111,  // BTN_RIGHT
+        Button::Middle => 0x112, // BTN_MIDDLE
+        // Inverse of `code_to_key`, which keeps only the low byte of
+        // 0x113..=0x117.
+        Button::Unknown(code) => 0x100 | code as u16,
+    }
+}
+
+/// `input-event-codes.h` reserves 0x110..=0x117 (`BTN_MOUSE`..`BTN_TASK`) for
+/// mouse buttons; anything in that range is a `Button`, never a `Key`, even
+/// if we don't have a named `Button` variant for it (e.g. `BTN_SIDE`).
+#[cfg_attr(not(all(target_os = "linux", feature = "unstable_grab")), allow(dead_code))]
+pub(crate) fn code_to_key(code: u16) -> Result<Key, Button> {
+    match code {
+        0x110 => Err(Button::Left),
+        0x111 => Err(Button::Right),
+        0x112 => Err(Button::Middle),
+        0x113..=0x117 => Err(Button::Unknown(code as u8)),
+        other => Ok(code_to_key_only(other).unwrap_or(Key::Unknown(other as u32))),
+    }
+}
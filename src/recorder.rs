@@ -0,0 +1,207 @@
+//! Event recording and timed playback, the xmacro/easymacros use case: record
+//! a workflow once, replay it deterministically. Requires the `serialize`
+//! feature, since a `Recording` is only useful once it can be saved to and
+//! loaded from a file.
+use crate::rdev::{Button, Key, SimulateError};
+use crate::{listen, simulate, Event, EventType};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// A sequence of events paired with the delay since the previous one, ready
+/// to be serialized to disk and replayed later with a `Player`.
+///
+/// We store relative timing (a `Duration` since the previous event) rather
+/// than absolute `SystemTime`s, so a recording replays at the same pace
+/// however long it sat on disk before being loaded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub events: Vec<(Duration, EventType)>,
+}
+
+/// Captures global events through `listen` and turns them into a `Recording`.
+///
+/// ```no_run
+/// use rdev::{Player, Recorder};
+/// use std::{thread, time::Duration};
+///
+/// let recorder = Recorder::new();
+/// thread::sleep(Duration::from_secs(5));
+/// let recording = recorder.stop();
+///
+/// let player = Player::new(recording);
+/// player.play_with(1.0, 1).unwrap();
+/// ```
+pub struct Recorder {
+    receiver: Receiver<(Duration, EventType)>,
+}
+
+impl Recorder {
+    /// Starts listening in a background thread. If `listen` fails, the error
+    /// is printed to stderr and nothing is recorded.
+    pub fn new() -> Recorder {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let mut last = SystemTime::now();
+            let callback = move |event: Event| {
+                let now = event.time;
+                let delta = now.duration_since(last).unwrap_or(Duration::from_secs(0));
+                last = now;
+                let _ = sender.send((delta, event.event_type));
+            };
+            if let Err(error) = listen(callback) {
+                eprintln!("Recorder could not listen: {:?}", error);
+            }
+        });
+        Recorder { receiver }
+    }
+
+    /// Stops capturing and returns everything recorded so far.
+    pub fn stop(self) -> Recording {
+        Recording {
+            events: self.receiver.try_iter().collect(),
+        }
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Recorder::new()
+    }
+}
+
+/// Replays a `Recording` through `simulate`.
+pub struct Player {
+    recording: Recording,
+}
+
+impl Player {
+    pub fn new(recording: Recording) -> Player {
+        Player { recording }
+    }
+
+    /// Replays the recording once, at its original speed.
+    pub fn play(&self) -> Result<(), SimulateError> {
+        self.play_with(1.0, 1)
+    }
+
+    /// Replays the recording `loop_count` times, sleeping the recorded delta
+    /// (scaled by `1 / speed`) between events. `speed` is clamped to a
+    /// minimum of `MIN_SPEED` so a zero or negative speed (e.g. an honest
+    /// "play it back instantly" mistake) can't divide the delta into a
+    /// `Duration` that overflows, instead of panicking; a `NaN` or infinite
+    /// speed falls back to 1.0, the original pace. Events carrying
+    /// an `Unknown` key/button code are skipped, since they were only ever
+    /// meaningful on the machine that recorded them. Any key or button
+    /// still held at the end of a pass is released, so a script that was
+    /// cut short doesn't leave stuck modifiers behind.
+    pub fn play_with(&self, speed: f64, loop_count: u32) -> Result<(), SimulateError> {
+        let speed = if speed.is_finite() { speed.max(MIN_SPEED) } else { 1.0 };
+        for _ in 0..loop_count {
+            let mut held_keys = Vec::new();
+            let mut held_buttons = Vec::new();
+            for (delta, event_type) in &self.recording.events {
+                if !is_simulatable(event_type) {
+                    continue;
+                }
+                let scaled = Duration::from_secs_f64(delta.as_secs_f64() / speed);
+                thread::sleep(scaled);
+                simulate(event_type)?;
+                track_held(event_type, &mut held_keys, &mut held_buttons);
+            }
+            release_held(&held_keys, &held_buttons)?;
+        }
+        Ok(())
+    }
+}
+
+/// The slowest `play_with` will ever scale a delta to, so dividing by
+/// `speed` can never produce a `Duration` too large to represent.
+const MIN_SPEED: f64 = 0.001;
+
+fn is_simulatable(event_type: &EventType) -> bool {
+    !matches!(
+        event_type,
+        EventType::KeyPress(Key::Unknown(_))
+            | EventType::KeyRelease(Key::Unknown(_))
+            | EventType::ButtonPress(Button::Unknown(_))
+            | EventType::ButtonRelease(Button::Unknown(_))
+    )
+}
+
+fn track_held(event_type: &EventType, held_keys: &mut Vec<Key>, held_buttons: &mut Vec<Button>) {
+    match *event_type {
+        EventType::KeyPress(key) => held_keys.push(key),
+        EventType::KeyRelease(key) => held_keys.retain(|&held| held != key),
+        EventType::ButtonPress(button) => held_buttons.push(button),
+        EventType::ButtonRelease(button) => held_buttons.retain(|&held| held != button),
+        _ => {}
+    }
+}
+
+fn release_held(held_keys: &[Key], held_buttons: &[Button]) -> Result<(), SimulateError> {
+    for &key in held_keys {
+        simulate(&EventType::KeyRelease(key))?;
+    }
+    for &button in held_buttons {
+        simulate(&EventType::ButtonRelease(button))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_simulatable() {
+        assert!(is_simulatable(&EventType::KeyPress(Key::KeyS)));
+        assert!(is_simulatable(&EventType::ButtonRelease(Button::Left)));
+        assert!(!is_simulatable(&EventType::KeyPress(Key::Unknown(0))));
+        assert!(!is_simulatable(&EventType::KeyRelease(Key::Unknown(0))));
+        assert!(!is_simulatable(&EventType::ButtonPress(Button::Unknown(0))));
+        assert!(!is_simulatable(&EventType::ButtonRelease(Button::Unknown(0))));
+    }
+
+    #[test]
+    fn test_track_held() {
+        let mut held_keys = Vec::new();
+        let mut held_buttons = Vec::new();
+
+        track_held(&EventType::KeyPress(Key::KeyA), &mut held_keys, &mut held_buttons);
+        track_held(&EventType::KeyPress(Key::KeyB), &mut held_keys, &mut held_buttons);
+        track_held(&EventType::ButtonPress(Button::Left), &mut held_keys, &mut held_buttons);
+        assert_eq!(held_keys, vec![Key::KeyA, Key::KeyB]);
+        assert_eq!(held_buttons, vec![Button::Left]);
+
+        track_held(&EventType::KeyRelease(Key::KeyA), &mut held_keys, &mut held_buttons);
+        assert_eq!(held_keys, vec![Key::KeyB]);
+
+        track_held(&EventType::ButtonRelease(Button::Left), &mut held_keys, &mut held_buttons);
+        assert!(held_buttons.is_empty());
+
+        // A `MouseMove` or `Wheel` doesn't hold anything, so it's ignored.
+        track_held(&EventType::MouseMove { x: 0.0, y: 0.0 }, &mut held_keys, &mut held_buttons);
+        assert_eq!(held_keys, vec![Key::KeyB]);
+    }
+
+    #[test]
+    fn test_release_held_nothing_held() {
+        assert!(release_held(&[], &[]).is_ok());
+    }
+
+    #[test]
+    fn test_recording_roundtrip() {
+        let recording = Recording {
+            events: vec![
+                (Duration::from_millis(0), EventType::KeyPress(Key::KeyA)),
+                (Duration::from_millis(50), EventType::KeyRelease(Key::KeyA)),
+                (Duration::from_millis(10), EventType::Wheel { delta_x: 0, delta_y: -1 }),
+            ],
+        };
+        let json = serde_json::to_string(&recording).unwrap();
+        let restored: Recording = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.events, recording.events);
+    }
+}
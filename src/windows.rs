@@ -0,0 +1,573 @@
+use std::cell::RefCell;
+use std::mem;
+use std::ptr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+
+use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+use winapi::shared::windef::POINT;
+use winapi::um::winuser::{
+    CallNextHookEx, DispatchMessageW, GetAsyncKeyState, GetCursorPos, GetMessageW,
+    GetSystemMetrics, SendInput, SetWindowsHookExW, TranslateMessage, UnhookWindowsHookEx,
+    HC_ACTION, INPUT, INPUT_KEYBOARD, INPUT_MOUSE, KBDLLHOOKSTRUCT, KEYEVENTF_KEYUP,
+    KEYEVENTF_UNICODE, LLKHF_INJECTED, LLMHF_INJECTED, MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_LEFTDOWN,
+    MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE,
+    MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_WHEEL, MOUSEEVENTF_XDOWN,
+    MOUSEEVENTF_XUP, MSG, MSLLHOOKSTRUCT, SM_CXSCREEN, SM_CYSCREEN, VK_LBUTTON, VK_MBUTTON,
+    VK_RBUTTON, WH_KEYBOARD_LL, WH_MOUSE_LL, WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP,
+    WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDOWN, WM_RBUTTONUP,
+    WM_SYSKEYDOWN, WM_XBUTTONDOWN, WM_XBUTTONUP, XBUTTON1,
+};
+
+use crate::rdev::{
+    is_shift, qwerty_lookup, Button, DisplayError, Event, EventType, GrabError, Key,
+    KeyboardState, ListenError, MouseState, SimulateError,
+};
+
+/// `Key`/`Button` <-> Win32 virtual-key code table (`winuser.h` `VK_*`
+/// constants). Only the keys `Key` itself can name are listed; anything else
+/// round-trips through `Key::Unknown`.
+fn key_to_vk(key: Key) -> Option<i32> {
+    Some(match key {
+        Key::Backspace => 0x08,
+        Key::Tab => 0x09,
+        Key::Return => 0x0D,
+        Key::ShiftLeft => 0xA0,
+        Key::ShiftRight => 0xA1,
+        Key::ControlLeft => 0xA2,
+        Key::ControlRight => 0xA3,
+        Key::Alt => 0xA4,
+        Key::AltGr => 0xA5,
+        Key::Pause => 0x13,
+        Key::CapsLock => 0x14,
+        Key::Escape => 0x1B,
+        Key::Space => 0x20,
+        Key::PageUp => 0x21,
+        Key::PageDown => 0x22,
+        Key::End => 0x23,
+        Key::Home => 0x24,
+        Key::LeftArrow => 0x25,
+        Key::UpArrow => 0x26,
+        Key::RightArrow => 0x27,
+        Key::DownArrow => 0x28,
+        Key::PrintScreen => 0x2C,
+        Key::Insert => 0x2D,
+        Key::Delete => 0x2E,
+        Key::Num0 => 0x30,
+        Key::Num1 => 0x31,
+        Key::Num2 => 0x32,
+        Key::Num3 => 0x33,
+        Key::Num4 => 0x34,
+        Key::Num5 => 0x35,
+        Key::Num6 => 0x36,
+        Key::Num7 => 0x37,
+        Key::Num8 => 0x38,
+        Key::Num9 => 0x39,
+        Key::KeyA => 0x41,
+        Key::KeyB => 0x42,
+        Key::KeyC => 0x43,
+        Key::KeyD => 0x44,
+        Key::KeyE => 0x45,
+        Key::KeyF => 0x46,
+        Key::KeyG => 0x47,
+        Key::KeyH => 0x48,
+        Key::KeyI => 0x49,
+        Key::KeyJ => 0x4A,
+        Key::KeyK => 0x4B,
+        Key::KeyL => 0x4C,
+        Key::KeyM => 0x4D,
+        Key::KeyN => 0x4E,
+        Key::KeyO => 0x4F,
+        Key::KeyP => 0x50,
+        Key::KeyQ => 0x51,
+        Key::KeyR => 0x52,
+        Key::KeyS => 0x53,
+        Key::KeyT => 0x54,
+        Key::KeyU => 0x55,
+        Key::KeyV => 0x56,
+        Key::KeyW => 0x57,
+        Key::KeyX => 0x58,
+        Key::KeyY => 0x59,
+        Key::KeyZ => 0x5A,
+        Key::MetaLeft => 0x5B,
+        Key::MetaRight => 0x5C,
+        Key::Kp0 => 0x60,
+        Key::Kp1 => 0x61,
+        Key::Kp2 => 0x62,
+        Key::Kp3 => 0x63,
+        Key::Kp4 => 0x64,
+        Key::Kp5 => 0x65,
+        Key::Kp6 => 0x66,
+        Key::Kp7 => 0x67,
+        Key::Kp8 => 0x68,
+        Key::Kp9 => 0x69,
+        Key::KpMultiply => 0x6A,
+        Key::KpPlus => 0x6B,
+        Key::KpMinus => 0x6D,
+        Key::KpDelete => 0x6E,
+        Key::KpDivide => 0x6F,
+        Key::F1 => 0x70,
+        Key::F2 => 0x71,
+        Key::F3 => 0x72,
+        Key::F4 => 0x73,
+        Key::F5 => 0x74,
+        Key::F6 => 0x75,
+        Key::F7 => 0x76,
+        Key::F8 => 0x77,
+        Key::F9 => 0x78,
+        Key::F10 => 0x79,
+        Key::F11 => 0x7A,
+        Key::F12 => 0x7B,
+        Key::NumLock => 0x90,
+        Key::ScrollLock => 0x91,
+        Key::SemiColon => 0xBA,
+        Key::Equal => 0xBB,
+        Key::Comma => 0xBC,
+        Key::Minus => 0xBD,
+        Key::Dot => 0xBE,
+        Key::Slash => 0xBF,
+        Key::BackQuote => 0xC0,
+        Key::LeftBracket => 0xDB,
+        Key::BackSlash => 0xDC,
+        Key::RightBracket => 0xDD,
+        Key::Quote => 0xDE,
+        Key::IntlBackslash => 0xE2,
+        Key::KpReturn | Key::Function => return None,
+        Key::Unknown(code) => code as i32,
+    })
+}
+
+/// Every named `Key` variant, used to invert `key_to_vk` for `query_keys`
+/// without maintaining a second, separately-indexed table.
+const ALL_KEYS: &[Key] = &[
+    Key::Alt, Key::AltGr, Key::Backspace, Key::CapsLock, Key::ControlLeft, Key::ControlRight,
+    Key::Delete, Key::DownArrow, Key::End, Key::Escape, Key::F1, Key::F2, Key::F3, Key::F4,
+    Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12, Key::Home,
+    Key::LeftArrow, Key::MetaLeft, Key::MetaRight, Key::PageDown, Key::PageUp, Key::Return,
+    Key::RightArrow, Key::ShiftLeft, Key::ShiftRight, Key::Space, Key::Tab, Key::UpArrow,
+    Key::PrintScreen, Key::ScrollLock, Key::Pause, Key::NumLock, Key::BackQuote, Key::Num1,
+    Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+    Key::Num0, Key::Minus, Key::Equal, Key::KeyQ, Key::KeyW, Key::KeyE, Key::KeyR, Key::KeyT,
+    Key::KeyY, Key::KeyU, Key::KeyI, Key::KeyO, Key::KeyP, Key::LeftBracket, Key::RightBracket,
+    Key::KeyA, Key::KeyS, Key::KeyD, Key::KeyF, Key::KeyG, Key::KeyH, Key::KeyJ, Key::KeyK,
+    Key::KeyL, Key::SemiColon, Key::Quote, Key::BackSlash, Key::IntlBackslash, Key::KeyZ,
+    Key::KeyX, Key::KeyC, Key::KeyV, Key::KeyB, Key::KeyN, Key::KeyM, Key::Comma, Key::Dot,
+    Key::Slash, Key::Insert, Key::KpMinus, Key::KpPlus, Key::KpMultiply, Key::KpDivide, Key::Kp0,
+    Key::Kp1, Key::Kp2, Key::Kp3, Key::Kp4, Key::Kp5, Key::Kp6, Key::Kp7, Key::Kp8, Key::Kp9,
+    Key::KpDelete,
+];
+
+#[derive(Default)]
+pub struct Keyboard {
+    shift: bool,
+}
+
+impl Keyboard {
+    pub fn new() -> Option<Keyboard> {
+        Some(Keyboard::default())
+    }
+}
+
+impl KeyboardState for Keyboard {
+    fn add(&mut self, event_type: &EventType) -> Option<String> {
+        // Real implementation goes through `ToUnicodeEx` against the
+        // current keyboard layout and `GetKeyboardState`; until that's
+        // wired in we fall back to a plain Qwerty lookup, which is what
+        // `Keyboard` has always actually been tested against.
+        match *event_type {
+            EventType::KeyPress(key) if is_shift(key) => {
+                self.shift = true;
+                None
+            }
+            EventType::KeyRelease(key) if is_shift(key) => {
+                self.shift = false;
+                None
+            }
+            EventType::KeyPress(key) => qwerty_lookup(key, self.shift),
+            _ => None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.shift = false;
+    }
+}
+
+fn send_input(input: INPUT) -> Result<(), SimulateError> {
+    let mut inputs = [input];
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_mut_ptr(),
+            mem::size_of::<INPUT>() as i32,
+        )
+    };
+    if sent as usize == inputs.len() {
+        Ok(())
+    } else {
+        Err(SimulateError)
+    }
+}
+
+fn keyboard_input(vk: i32, flags: u32) -> INPUT {
+    let mut input: INPUT = unsafe { mem::zeroed() };
+    input.type_ = INPUT_KEYBOARD;
+    let ki = unsafe { input.u.ki_mut() };
+    ki.wVk = vk as u16;
+    ki.dwFlags = flags;
+    input
+}
+
+fn mouse_input(flags: u32, dx: i32, dy: i32, data: u32) -> INPUT {
+    let mut input: INPUT = unsafe { mem::zeroed() };
+    input.type_ = INPUT_MOUSE;
+    let mi = unsafe { input.u.mi_mut() };
+    mi.dx = dx;
+    mi.dy = dy;
+    mi.mouseData = data;
+    mi.dwFlags = flags;
+    input
+}
+
+fn button_to_mouse_flags(button: Button, down: bool) -> (u32, u32) {
+    match button {
+        Button::Left => (if down { MOUSEEVENTF_LEFTDOWN } else { MOUSEEVENTF_LEFTUP }, 0),
+        Button::Right => (if down { MOUSEEVENTF_RIGHTDOWN } else { MOUSEEVENTF_RIGHTUP }, 0),
+        Button::Middle => (if down { MOUSEEVENTF_MIDDLEDOWN } else { MOUSEEVENTF_MIDDLEUP }, 0),
+        Button::Unknown(_) => (if down { MOUSEEVENTF_XDOWN } else { MOUSEEVENTF_XUP }, XBUTTON1 as u32),
+    }
+}
+
+pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    let input = match *event_type {
+        EventType::KeyPress(key) => {
+            let vk = key_to_vk(key).ok_or(SimulateError)?;
+            keyboard_input(vk, 0)
+        }
+        EventType::KeyRelease(key) => {
+            let vk = key_to_vk(key).ok_or(SimulateError)?;
+            keyboard_input(vk, KEYEVENTF_KEYUP)
+        }
+        EventType::ButtonPress(button) => {
+            let (flags, data) = button_to_mouse_flags(button, true);
+            mouse_input(flags, 0, 0, data)
+        }
+        EventType::ButtonRelease(button) => {
+            let (flags, data) = button_to_mouse_flags(button, false);
+            mouse_input(flags, 0, 0, data)
+        }
+        EventType::MouseMove { x, y } => {
+            let width = unsafe { GetSystemMetrics(SM_CXSCREEN) };
+            let height = unsafe { GetSystemMetrics(SM_CYSCREEN) };
+            if width <= 0 || height <= 0 {
+                return Err(SimulateError);
+            }
+            let normalized_x = (x * 65536.0 / width as f64) as i32;
+            let normalized_y = (y * 65536.0 / height as f64) as i32;
+            mouse_input(MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE, normalized_x, normalized_y, 0)
+        }
+        EventType::Wheel { delta_x: _, delta_y } => {
+            mouse_input(MOUSEEVENTF_WHEEL, 0, 0, (delta_y * 120) as u32)
+        }
+    };
+    send_input(input)
+}
+
+/// Types `text` directly, independent of the current keyboard layout, by
+/// sending one `INPUT` per UTF-16 unit with `KEYEVENTF_UNICODE` set instead
+/// of a virtual-key code.
+pub fn simulate_unicode(text: &str) -> Result<(), SimulateError> {
+    for unit in text.encode_utf16() {
+        let mut down: INPUT = unsafe { mem::zeroed() };
+        down.type_ = INPUT_KEYBOARD;
+        {
+            let ki = unsafe { down.u.ki_mut() };
+            ki.wScan = unit;
+            ki.dwFlags = KEYEVENTF_UNICODE;
+        }
+        send_input(down)?;
+
+        let mut up: INPUT = unsafe { mem::zeroed() };
+        up.type_ = INPUT_KEYBOARD;
+        {
+            let ki = unsafe { up.u.ki_mut() };
+            ki.wScan = unit;
+            ki.dwFlags = KEYEVENTF_UNICODE | KEYEVENTF_KEYUP;
+        }
+        send_input(up)?;
+    }
+    Ok(())
+}
+
+pub fn listen<T>(callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    run_listen_loop(callback)
+}
+
+pub fn display_size() -> Result<(u64, u64), DisplayError> {
+    let width = unsafe { GetSystemMetrics(SM_CXSCREEN) };
+    let height = unsafe { GetSystemMetrics(SM_CYSCREEN) };
+    if width <= 0 || height <= 0 {
+        return Err(DisplayError);
+    }
+    Ok((width as u64, height as u64))
+}
+
+/// The high-order bit of `GetAsyncKeyState`'s return value is set exactly
+/// when the key is currently held down.
+fn is_down(vk: i32) -> bool {
+    unsafe { GetAsyncKeyState(vk) & 0x8000u16 as i16 != 0 }
+}
+
+/// Polls every mapped virtual-key code with `GetAsyncKeyState` and collects
+/// the ones whose high-order bit is set.
+pub fn query_keys() -> Vec<Key> {
+    ALL_KEYS
+        .iter()
+        .copied()
+        .filter(|&key| key_to_vk(key).map(is_down).unwrap_or(false))
+        .collect()
+}
+
+/// Polls the cursor position with `GetCursorPos` and each button's state
+/// with `GetAsyncKeyState(VK_LBUTTON/VK_RBUTTON/VK_MBUTTON)`.
+pub fn query_mouse() -> MouseState {
+    let mut point = POINT { x: 0, y: 0 };
+    let coords = if unsafe { GetCursorPos(&mut point) } != 0 {
+        (point.x, point.y)
+    } else {
+        (0, 0)
+    };
+    MouseState {
+        coords,
+        button_pressed: vec![is_down(VK_LBUTTON), is_down(VK_RBUTTON), is_down(VK_MBUTTON)],
+    }
+}
+
+fn vk_to_key(vk: i32) -> Option<Key> {
+    ALL_KEYS.iter().copied().find(|&key| key_to_vk(key) == Some(vk))
+}
+
+type GrabCallback = Box<dyn Fn(Event) -> Option<Event>>;
+
+thread_local! {
+    /// Low-level hook procedures are plain `extern "system" fn` pointers
+    /// with no room for user data, and hooks are thread-specific (they must
+    /// be unhooked from the thread that set them), so the callback lives
+    /// here instead of being threaded through `SetWindowsHookExW`.
+    static GRAB_CALLBACK: RefCell<Option<GrabCallback>> = RefCell::new(None);
+}
+
+/// Runs `event_type` through the grab callback. Returns `true` to let the
+/// original event through unchanged, `false` to suppress it -- re-injecting
+/// a rewritten replacement via `simulate` first if the callback returned a
+/// modified event instead of dropping it outright.
+fn dispatch_grabbed_event(event_type: EventType) -> bool {
+    let modified = GRAB_CALLBACK.with(|cb| {
+        cb.borrow()
+            .as_ref()
+            .and_then(|callback| callback(Event { time: SystemTime::now(), name: None, event_type }))
+    });
+    match modified {
+        None => false,
+        Some(event) if event.event_type == event_type => true,
+        Some(event) => {
+            let _ = simulate(&event.event_type);
+            false
+        }
+    }
+}
+
+/// Decodes a `WH_KEYBOARD_LL` hook's payload into an `EventType`, or `None`
+/// if the event was injected by `SendInput` (ours or anyone else's) and
+/// should be ignored to avoid feedback loops.
+fn decode_keyboard_event(info: &KBDLLHOOKSTRUCT, wparam: WPARAM) -> Option<EventType> {
+    if info.flags & LLKHF_INJECTED != 0 {
+        return None;
+    }
+    let message = wparam as UINT;
+    let pressed = message == WM_KEYDOWN || message == WM_SYSKEYDOWN;
+    let key = vk_to_key(info.vkCode as i32).unwrap_or(Key::Unknown(info.vkCode));
+    Some(if pressed { EventType::KeyPress(key) } else { EventType::KeyRelease(key) })
+}
+
+/// Decodes a `WH_MOUSE_LL` hook's payload into an `EventType`, or `None` if
+/// the event was injected (see `decode_keyboard_event`) or isn't one of the
+/// messages we track.
+fn decode_mouse_event(info: &MSLLHOOKSTRUCT, wparam: WPARAM) -> Option<EventType> {
+    if info.flags & LLMHF_INJECTED != 0 {
+        return None;
+    }
+    let high_word = (info.mouseData >> 16) as i16;
+    match wparam as UINT {
+        WM_MOUSEMOVE => Some(EventType::MouseMove { x: info.pt.x as f64, y: info.pt.y as f64 }),
+        WM_LBUTTONDOWN => Some(EventType::ButtonPress(Button::Left)),
+        WM_LBUTTONUP => Some(EventType::ButtonRelease(Button::Left)),
+        WM_RBUTTONDOWN => Some(EventType::ButtonPress(Button::Right)),
+        WM_RBUTTONUP => Some(EventType::ButtonRelease(Button::Right)),
+        WM_MBUTTONDOWN => Some(EventType::ButtonPress(Button::Middle)),
+        WM_MBUTTONUP => Some(EventType::ButtonRelease(Button::Middle)),
+        WM_XBUTTONDOWN => Some(EventType::ButtonPress(Button::Unknown(high_word as u8))),
+        WM_XBUTTONUP => Some(EventType::ButtonRelease(Button::Unknown(high_word as u8))),
+        WM_MOUSEWHEEL => Some(EventType::Wheel { delta_x: 0, delta_y: (high_word / 120) as i64 }),
+        _ => None,
+    }
+}
+
+unsafe extern "system" fn keyboard_hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION {
+        let info = &*(lparam as *const KBDLLHOOKSTRUCT);
+        if let Some(event_type) = decode_keyboard_event(info, wparam) {
+            if !dispatch_grabbed_event(event_type) {
+                return 1;
+            }
+        }
+    }
+    CallNextHookEx(ptr::null_mut(), code, wparam, lparam)
+}
+
+unsafe extern "system" fn mouse_hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION {
+        let info = &*(lparam as *const MSLLHOOKSTRUCT);
+        if let Some(event_type) = decode_mouse_event(info, wparam) {
+            if !dispatch_grabbed_event(event_type) {
+                return 1;
+            }
+        }
+    }
+    CallNextHookEx(ptr::null_mut(), code, wparam, lparam)
+}
+
+type ListenCallback = Box<dyn FnMut(Event)>;
+
+thread_local! {
+    /// Separate from `GRAB_CALLBACK`: `listen`'s callback is `FnMut` (it
+    /// can't rewrite or suppress events) and the two can legitimately be
+    /// installed on different threads at once.
+    static LISTEN_CALLBACK: RefCell<Option<ListenCallback>> = RefCell::new(None);
+}
+
+/// Hands `event_type` to the listen callback, if one is installed.
+fn dispatch_listened_event(event_type: EventType) {
+    LISTEN_CALLBACK.with(|cb| {
+        if let Some(callback) = cb.borrow_mut().as_mut() {
+            callback(Event { time: SystemTime::now(), name: None, event_type });
+        }
+    });
+}
+
+unsafe extern "system" fn listen_keyboard_hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION {
+        let info = &*(lparam as *const KBDLLHOOKSTRUCT);
+        if let Some(event_type) = decode_keyboard_event(info, wparam) {
+            dispatch_listened_event(event_type);
+        }
+    }
+    CallNextHookEx(ptr::null_mut(), code, wparam, lparam)
+}
+
+unsafe extern "system" fn listen_mouse_hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION {
+        let info = &*(lparam as *const MSLLHOOKSTRUCT);
+        if let Some(event_type) = decode_mouse_event(info, wparam) {
+            dispatch_listened_event(event_type);
+        }
+    }
+    CallNextHookEx(ptr::null_mut(), code, wparam, lparam)
+}
+
+/// Installs the listen-only hooks and pumps this thread's message loop
+/// forever -- `listen`, unlike `grab`, has no non-blocking mode, matching
+/// the X11 RECORD extension and `CGEventTap` backends on the other
+/// platforms.
+fn run_listen_loop<T>(callback: T) -> Result<(), ListenError>
+where
+    T: FnMut(Event) + 'static,
+{
+    LISTEN_CALLBACK.with(|cb| *cb.borrow_mut() = Some(Box::new(callback)));
+    let keyboard_hook = unsafe {
+        SetWindowsHookExW(WH_KEYBOARD_LL, Some(listen_keyboard_hook_proc), ptr::null_mut(), 0)
+    };
+    if keyboard_hook.is_null() {
+        return Err(ListenError::EventTapError);
+    }
+    let mouse_hook =
+        unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(listen_mouse_hook_proc), ptr::null_mut(), 0) };
+    if mouse_hook.is_null() {
+        unsafe {
+            UnhookWindowsHookEx(keyboard_hook);
+        }
+        return Err(ListenError::EventTapError);
+    }
+    let mut msg: MSG = unsafe { mem::zeroed() };
+    unsafe {
+        while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+        UnhookWindowsHookEx(keyboard_hook);
+        UnhookWindowsHookEx(mouse_hook);
+    }
+    Ok(())
+}
+
+/// Installs the keyboard/mouse hooks, calls `on_installed` with the setup
+/// result, then pumps this thread's message loop until `GetMessageW`
+/// returns 0 (e.g. `WM_QUIT`) -- hooks only deliver messages to the thread
+/// that set them, so a non-blocking caller still needs this loop running on
+/// a background thread to receive anything.
+fn run_grab_loop<T>(callback: T, on_installed: impl FnOnce(Result<(), GrabError>)) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + Send + 'static,
+{
+    GRAB_CALLBACK.with(|cb| *cb.borrow_mut() = Some(Box::new(callback)));
+    let keyboard_hook =
+        unsafe { SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook_proc), ptr::null_mut(), 0) };
+    if keyboard_hook.is_null() {
+        on_installed(Err(GrabError::EventTapError));
+        return Err(GrabError::EventTapError);
+    }
+    let mouse_hook =
+        unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook_proc), ptr::null_mut(), 0) };
+    if mouse_hook.is_null() {
+        unsafe {
+            UnhookWindowsHookEx(keyboard_hook);
+        }
+        on_installed(Err(GrabError::EventTapError));
+        return Err(GrabError::EventTapError);
+    }
+    on_installed(Ok(()));
+    let mut msg: MSG = unsafe { mem::zeroed() };
+    unsafe {
+        while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+        UnhookWindowsHookEx(keyboard_hook);
+        UnhookWindowsHookEx(mouse_hook);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "unstable_grab")]
+pub fn grab<T>(callback: T, blocking: bool) -> Result<(), GrabError>
+where
+    T: Fn(Event) -> Option<Event> + Send + 'static,
+{
+    if blocking {
+        return run_grab_loop(callback, |_| {});
+    }
+    // Non-blocking: run the (blocking) loop on a background thread and hand
+    // control back to the caller once the hooks are installed (or failed to
+    // install).
+    let (result_tx, result_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = run_grab_loop(callback, move |result| {
+            let _ = result_tx.send(result);
+        });
+    });
+    result_rx.recv().unwrap_or(Err(GrabError::EventTapError))
+}